@@ -0,0 +1,374 @@
+use crate::{CharData, FontToolError};
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::str::FromStr;
+
+/// The algorithm used to place the glyphs in the atlas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Packing {
+    /// place the glyphs in codepoint order, from left to right, starting a new row when the
+    /// current one is full
+    Shelf,
+    /// place the glyphs sorted by height, each at the lowest position of the skyline
+    Skyline,
+    /// place the glyphs sorted by height, each at the lowest position of the free rectangles
+    MaxRects,
+}
+
+impl FromStr for Packing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "shelf" => Self::Shelf,
+            "skyline" => Self::Skyline,
+            "maxrects" => Self::MaxRects,
+            _ => return Err(format!("unknown packing algorithm {:?}", s)),
+        })
+    }
+}
+
+/// The parameters used to generate the atlas
+#[derive(Debug, Clone)]
+pub struct AtlasOptions {
+    pub packing: Packing,
+    /// the width of the atlas. It is increased if a glyph is wider, and rounded to the next
+    /// multiple of 8 (or power of two)
+    pub width: u16,
+    /// make both the width and the height of the atlas a power of two
+    pub power_of_two: bool,
+    /// reuse the layout of the .img file the font was read from, if the glyphs still fit in it
+    pub keep_original_layout: bool,
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        Self {
+            packing: Packing::Shelf,
+            width: 512,
+            power_of_two: false,
+            keep_original_layout: true,
+        }
+    }
+}
+
+/// Information about a generated atlas
+#[derive(Debug, Clone)]
+pub struct AtlasReport {
+    pub width: u32,
+    pub height: u32,
+    /// the number of pixel covered by a glyph
+    pub used_pixels: u64,
+    /// true if the layout of the original .img file was reused
+    pub original_layout: bool,
+}
+
+impl AtlasReport {
+    /// The proportion of the atlas covered by a glyph, between 0 and 1
+    pub fn fill_ratio(&self) -> f64 {
+        let total = self.width as u64 * self.height as u64;
+        if total == 0 {
+            0.0
+        } else {
+            self.used_pixels as f64 / total as f64
+        }
+    }
+}
+
+/// The position of a glyph in the atlas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlyphPosition {
+    pub char_id: u16,
+    pub x: u16,
+    pub y: u16,
+}
+
+/// The placement of every glyph in the atlas
+#[derive(Debug, Clone)]
+pub struct AtlasLayout {
+    pub width: u32,
+    pub height: u32,
+    /// the position of every glyph, in the order they are written in the .dic file
+    pub glyphs: Vec<GlyphPosition>,
+}
+
+impl AtlasLayout {
+    /// Check that this layout contain every glyph exactly once, and that they doesn't go out of
+    /// the atlas or overlap (except for identical glyphs at the same position)
+    pub fn fits(&self, chars: &BTreeMap<u16, CharData>) -> bool {
+        if self.glyphs.len() != chars.len() {
+            return false;
+        }
+        let mut rects = Vec::with_capacity(self.glyphs.len());
+        for glyph in &self.glyphs {
+            let char_data = match chars.get(&glyph.char_id) {
+                Some(char_data) => char_data,
+                None => return false,
+            };
+            let rect = Rect {
+                x: glyph.x as u32,
+                y: glyph.y as u32,
+                width: char_data.glyth_width as u32,
+                height: char_data.glyth_height as u32,
+            };
+            if rect.x + rect.width > self.width || rect.y + rect.height > self.height {
+                return false;
+            }
+            rects.push((glyph.char_id, rect));
+        }
+        rects.sort_by_key(|(_, rect)| rect.x);
+        for (index, (char_id, rect)) in rects.iter().enumerate() {
+            for (other_char_id, other) in &rects[index + 1..] {
+                if other.x >= rect.x + rect.width {
+                    break;
+                }
+                if char_id == other_char_id {
+                    return false;
+                }
+                if rect.intersects(other)
+                    && (rect != other || chars[char_id].image != chars[other_char_id].image)
+                {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Rect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Rect {
+    fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.width <= self.x + self.width
+            && other.y + other.height <= self.y + self.height
+    }
+
+    fn intersects(&self, other: &Rect) -> bool {
+        other.x < self.x + self.width
+            && self.x < other.x + other.width
+            && other.y < self.y + self.height
+            && self.y < other.y + other.height
+    }
+}
+
+fn round_dimension(value: u32, power_of_two: bool) -> u32 {
+    let value = value.max(8);
+    if power_of_two {
+        value.next_power_of_two()
+    } else {
+        ((value - 1) / 8 + 1) * 8
+    }
+}
+
+/// Compute the position of glyphs of the given size (indexed by character id)
+pub(crate) fn pack(
+    sizes: &BTreeMap<u16, (u16, u16)>,
+    options: &AtlasOptions,
+) -> Result<AtlasLayout, FontToolError> {
+    let max_glyph_width = sizes.values().map(|s| s.0).max().unwrap_or(0);
+    let width = round_dimension(
+        options.width.max(max_glyph_width) as u32,
+        options.power_of_two,
+    );
+
+    let mut sorted: Vec<(u16, u32, u32)> = sizes
+        .iter()
+        .map(|(id, (w, h))| (*id, *w as u32, *h as u32))
+        .collect();
+    if options.packing != Packing::Shelf {
+        sorted.sort_by(|a, b| b.2.cmp(&a.2).then(b.1.cmp(&a.1)).then(a.0.cmp(&b.0)));
+    }
+
+    let positions = match options.packing {
+        Packing::Shelf => pack_shelf(&sorted, width),
+        Packing::Skyline => pack_skyline(&sorted, width),
+        Packing::MaxRects => pack_maxrects(&sorted, width),
+    };
+
+    let mut used_height = 0;
+    let mut glyphs = Vec::with_capacity(sizes.len());
+    for (char_id, (_, height)) in sizes {
+        let (x, y) = positions[char_id];
+        used_height = used_height.max(y + *height as u32);
+        glyphs.push(GlyphPosition {
+            char_id: *char_id,
+            x: x.try_into().map_err(|_| FontToolError::AtlasTooBig)?,
+            y: y.try_into().map_err(|_| FontToolError::AtlasTooBig)?,
+        });
+    }
+    let height = round_dimension(used_height, options.power_of_two);
+    if height > u16::MAX as u32 || width > u16::MAX as u32 {
+        return Err(FontToolError::AtlasTooBig);
+    }
+
+    Ok(AtlasLayout {
+        width,
+        height,
+        glyphs,
+    })
+}
+
+fn pack_shelf(glyphs: &[(u16, u32, u32)], atlas_width: u32) -> BTreeMap<u16, (u32, u32)> {
+    let mut positions = BTreeMap::new();
+    let mut lower_y = 0;
+    let mut pos_x = 0;
+    let mut pos_y = 0;
+    for (char_id, width, height) in glyphs {
+        if pos_x + width > atlas_width {
+            pos_x = 0;
+            pos_y = lower_y;
+        };
+        positions.insert(*char_id, (pos_x, pos_y));
+        lower_y = lower_y.max(pos_y + height);
+        pos_x += width;
+    }
+    positions
+}
+
+fn pack_skyline(glyphs: &[(u16, u32, u32)], atlas_width: u32) -> BTreeMap<u16, (u32, u32)> {
+    let mut positions = BTreeMap::new();
+    // each segment is (x, y, width), sorted by x and covering the whole width of the atlas
+    let mut skyline: Vec<(u32, u32, u32)> = vec![(0, 0, atlas_width)];
+    for (char_id, width, height) in glyphs {
+        if *width == 0 || *height == 0 {
+            positions.insert(*char_id, (0, 0));
+            continue;
+        }
+        // find the lowest position, then the leftmost one
+        let mut best: Option<(usize, u32, u32)> = None;
+        for start in 0..skyline.len() {
+            let x = skyline[start].0;
+            if x + width > atlas_width {
+                break;
+            }
+            let mut y = 0;
+            for segment in &skyline[start..] {
+                if segment.0 >= x + width {
+                    break;
+                }
+                y = y.max(segment.1);
+            }
+            if best.is_none_or(|(_, best_x, best_y)| (y, x) < (best_y, best_x)) {
+                best = Some((start, x, y));
+            }
+        }
+        // the atlas is at least as wide as the widest glyph, so there is always a position
+        let (start, x, y) = best.expect("no position in the skyline for a glyph");
+        positions.insert(*char_id, (x, y));
+
+        // update the skyline
+        let end_x = x + width;
+        let mut new_skyline = skyline[..start].to_vec();
+        new_skyline.push((x, y + height, *width));
+        for segment in &skyline[start..] {
+            let segment_end = segment.0 + segment.2;
+            if segment_end <= end_x {
+                continue;
+            }
+            if segment.0 < end_x {
+                new_skyline.push((end_x, segment.1, segment_end - end_x));
+            } else {
+                new_skyline.push(*segment);
+            }
+        }
+        // merge the adjacent segments at the same height
+        skyline = Vec::with_capacity(new_skyline.len());
+        for segment in new_skyline {
+            match skyline.last_mut() {
+                Some(last) if last.1 == segment.1 => last.2 += segment.2,
+                _ => skyline.push(segment),
+            }
+        }
+    }
+    positions
+}
+
+fn pack_maxrects(glyphs: &[(u16, u32, u32)], atlas_width: u32) -> BTreeMap<u16, (u32, u32)> {
+    let mut positions = BTreeMap::new();
+    let total_height = glyphs.iter().map(|g| g.2).sum::<u32>().max(1);
+    let mut free_rects = vec![Rect {
+        x: 0,
+        y: 0,
+        width: atlas_width,
+        height: total_height,
+    }];
+    for (char_id, width, height) in glyphs {
+        if *width == 0 || *height == 0 {
+            positions.insert(*char_id, (0, 0));
+            continue;
+        }
+        // bottom-left rule: the lowest top, then the leftmost position
+        let placed = free_rects
+            .iter()
+            .filter(|free| free.width >= *width && free.height >= *height)
+            .map(|free| Rect {
+                x: free.x,
+                y: free.y,
+                width: *width,
+                height: *height,
+            })
+            .min_by_key(|rect| (rect.y + rect.height, rect.x))
+            .expect("no free rectangle for a glyph");
+        positions.insert(*char_id, (placed.x, placed.y));
+
+        let mut new_free_rects = Vec::with_capacity(free_rects.len() + 4);
+        for free in free_rects {
+            if !free.intersects(&placed) {
+                new_free_rects.push(free);
+                continue;
+            }
+            if placed.x > free.x {
+                new_free_rects.push(Rect {
+                    width: placed.x - free.x,
+                    ..free
+                });
+            }
+            if placed.x + placed.width < free.x + free.width {
+                new_free_rects.push(Rect {
+                    x: placed.x + placed.width,
+                    width: free.x + free.width - placed.x - placed.width,
+                    ..free
+                });
+            }
+            if placed.y > free.y {
+                new_free_rects.push(Rect {
+                    height: placed.y - free.y,
+                    ..free
+                });
+            }
+            if placed.y + placed.height < free.y + free.height {
+                new_free_rects.push(Rect {
+                    y: placed.y + placed.height,
+                    height: free.y + free.height - placed.y - placed.height,
+                    ..free
+                });
+            }
+        }
+        // remove the free rectangles contained in another one
+        let mut pruned: Vec<Rect> = Vec::with_capacity(new_free_rects.len());
+        for (index, rect) in new_free_rects.iter().enumerate() {
+            let is_contained = new_free_rects
+                .iter()
+                .enumerate()
+                .any(|(other_index, other)| {
+                    other_index != index
+                        && other.contains(rect)
+                        && (!rect.contains(other) || other_index < index)
+                });
+            if !is_contained {
+                pruned.push(*rect);
+            }
+        }
+        free_rects = pruned;
+    }
+    positions
+}
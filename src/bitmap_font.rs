@@ -0,0 +1,755 @@
+use crate::text_font::{hex_digit, luminance_of};
+use crate::{
+    default_line_height, estimate_baseline, CharData, Font, FontToolError, DEFAULT_UNK4,
+    DEFAULT_UNK5,
+};
+use flate2::read::GzDecoder;
+use image::{Rgba, RgbaImage};
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fmt::Write;
+use std::io::Read;
+
+/// The content of a bitmap font
+struct ParsedBitmapFont {
+    ascent: i32,
+    glyphs: Vec<BitmapGlyph>,
+    /// the properties specific to this tool, used to store the unknown fields of the .dic file
+    pmd_properties: BTreeMap<String, String>,
+}
+
+/// A glyph read from a bitmap font, with the position of its bounding box relative to the origin
+/// on the baseline (y going up)
+struct BitmapGlyph {
+    encoding: u32,
+    x_offset: i32,
+    y_offset: i32,
+    advance: i32,
+    image: RgbaImage,
+}
+
+fn invalid(message: impl Into<String>) -> FontToolError {
+    FontToolError::InvalidBitmapFont(message.into())
+}
+
+/// Import a bitmap font in the BDF or PCF format (eventually compressed with gzip).
+///
+/// The glyphs are placed so the top of the line is `FONT_ASCENT` pixel above the baseline, and
+/// the DWIDTH of the glyphs is used as their distance. Glyphs outside of the Basic Multilingual
+/// Plane are ignored, as they can't be stored in a .dic file. The unknown fields and the luminance
+/// written by [`export_bdf`] are read back from the properties of the font, the glyphs being
+/// black (like the ones of the game) when the font doesn't have them.
+pub fn import_bitmap_font(data: &[u8]) -> Result<Font, FontToolError> {
+    if data.starts_with(&[0x1f, 0x8b]) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(data).read_to_end(&mut decompressed)?;
+        return import_bitmap_font(&decompressed);
+    };
+    let parsed = if data.starts_with(b"\x01fcp") {
+        parse_pcf(data)?
+    } else if data.starts_with(b"STARTFONT") {
+        parse_bdf(&String::from_utf8_lossy(data))?
+    } else {
+        return Err(invalid("the file isn't a BDF or PCF font"));
+    };
+
+    let property = |name: &str| parsed.pmd_properties.get(name);
+    let number_property = |name: &str, default| -> Result<u32, FontToolError> {
+        match property(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| invalid(format!("the property {} isn't a number", name))),
+            None => Ok(default),
+        }
+    };
+    let default_unk4 = number_property("PMD_UNK4", DEFAULT_UNK4 as u32)? as u16;
+    let default_unk5 = number_property("PMD_UNK5", DEFAULT_UNK5 as u32)? as u16;
+    let mut unk_overrides = BTreeMap::new();
+    if let Some(overrides) = property("PMD_UNK_OVERRIDES") {
+        for entry in overrides.split_whitespace() {
+            let values = entry
+                .split(':')
+                .map(|value| value.parse::<u16>())
+                .collect::<Result<Vec<_>, _>>();
+            match values.as_deref() {
+                Ok([char_id, unk4, unk5]) => unk_overrides.insert(*char_id, (*unk4, *unk5)),
+                _ => {
+                    return Err(invalid(format!(
+                        "invalid PMD_UNK_OVERRIDES entry {:?}",
+                        entry
+                    )))
+                }
+            };
+        }
+    };
+
+    // the fonts of the game are black, with the shape of the glyphs stored in the alpha
+    let default_luminance = match property("PMD_LUMINANCE") {
+        Some(value) => parse_luminance(value)?,
+        None => vec![0],
+    };
+    let mut luminance_overrides = BTreeMap::new();
+    if let Some(overrides) = property("PMD_LUMINANCE_OVERRIDES") {
+        for entry in overrides.split_whitespace() {
+            let invalid_entry =
+                || invalid(format!("invalid PMD_LUMINANCE_OVERRIDES entry {:?}", entry));
+            let (char_id, luminance) = entry.split_once(':').ok_or_else(invalid_entry)?;
+            let char_id: u16 = char_id.parse().map_err(|_| invalid_entry())?;
+            luminance_overrides.insert(char_id, parse_luminance(luminance)?);
+        }
+    };
+
+    let mut font = Font {
+        dic_unk1: number_property("PMD_DIC_UNK1", 0)?,
+        dic_unk2: number_property("PMD_DIC_UNK2", 0)?,
+        ..Font::default()
+    };
+    for glyph in parsed.glyphs {
+        let char_id: u16 = match glyph.encoding.try_into() {
+            Ok(char_id) => char_id,
+            Err(_) => continue,
+        };
+        let (mut image, xalign, yalign) = if glyph.image.width() == 0 || glyph.image.height() == 0 {
+            (RgbaImage::new(1, 1), 0, 0)
+        } else {
+            let top = glyph.y_offset + glyph.image.height() as i32;
+            (glyph.image, glyph.x_offset, parsed.ascent - top)
+        };
+        let luminance = luminance_overrides
+            .get(&char_id)
+            .unwrap_or(&default_luminance);
+        if luminance.len() != 1 && luminance.len() != image.pixels().len() {
+            return Err(invalid(format!(
+                "the luminance of the character {} doesn't have a value per pixel",
+                char_id
+            )));
+        };
+        for (index, pixel) in image.pixels_mut().enumerate() {
+            let value = luminance[index % luminance.len()];
+            *pixel = Rgba([value, value, value, pixel[3]]);
+        }
+        let (unk4, unk5) = unk_overrides
+            .get(&char_id)
+            .copied()
+            .unwrap_or((default_unk4, default_unk5));
+        let char_data = CharData::new(
+            char_id,
+            image,
+            xalign as i16,
+            yalign as i16,
+            glyph.advance.max(0) as u16,
+            unk4,
+            unk5,
+        )?;
+        font.chars.insert(char_id, char_data);
+    }
+    Ok(font)
+}
+
+/// Parse the luminance of a glyph, written by [`glyph_luminance`]
+fn parse_luminance(text: &str) -> Result<Vec<u8>, FontToolError> {
+    text.chars()
+        .map(|digit| digit.to_digit(16).map(|digit| digit as u8))
+        .collect::<Option<Vec<u8>>>()
+        .filter(|luminance| !luminance.is_empty())
+        .ok_or_else(|| invalid(format!("invalid luminance {:?}", text)))
+}
+
+/// The luminance of a glyph, as a single hexadecimal digit when every pixel has the same, or as
+/// a digit per pixel otherwise
+fn glyph_luminance(image: &RgbaImage) -> String {
+    let luminances: Vec<u8> = image.pixels().map(luminance_of).collect();
+    let uniform = luminances.windows(2).all(|pair| pair[0] == pair[1]);
+    let luminances = if uniform && !luminances.is_empty() {
+        &luminances[..1]
+    } else {
+        &luminances[..]
+    };
+    luminances.iter().map(|value| hex_digit(*value)).collect()
+}
+
+/// How the alpha of the glyphs is stored in an exported BDF font
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BdfAlpha {
+    /// a pixel is set if its alpha is at least this value
+    Threshold(u8),
+    /// store the 4 bit alpha of the game as a BDF 2.3 greymap with 4 bit per pixel
+    Greymap,
+}
+
+/// Export the font to the BDF format.
+///
+/// The xalign, yalign and distance of the glyphs are stored in their BBX and DWIDTH, with the
+/// baseline guessed by [`estimate_baseline`]. The header of the .dic file and the unk4 and unk5
+/// values are stored in the PMD_DIC_UNK1, PMD_DIC_UNK2, PMD_UNK4, PMD_UNK5 (the most common
+/// values) and PMD_UNK_OVERRIDES (as `char_id:unk4:unk5` for the other characters) properties.
+/// The luminance of the glyphs, that BDF can't store, is kept in the same way in the
+/// PMD_LUMINANCE and PMD_LUMINANCE_OVERRIDES properties, as an hexadecimal digit for the whole
+/// glyph or one for each pixel.
+pub fn export_bdf(font: &Font, name: &str, alpha: BdfAlpha) -> Result<String, FontToolError> {
+    let ascent = estimate_baseline(font) as i32;
+    let line_height = default_line_height(font) as i32;
+    let descent = (line_height - ascent).max(0);
+    let bits_per_pixel = match alpha {
+        BdfAlpha::Threshold(_) => 1,
+        BdfAlpha::Greymap => 4,
+    };
+
+    let mut unk_count: BTreeMap<(u16, u16), usize> = BTreeMap::new();
+    for char_data in font.chars.values() {
+        *unk_count
+            .entry((char_data.unk4, char_data.unk5))
+            .or_default() += 1;
+    }
+    let (default_unk4, default_unk5) = unk_count
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(unk, _)| unk)
+        .unwrap_or((DEFAULT_UNK4, DEFAULT_UNK5));
+    let unk_overrides: Vec<String> = font
+        .chars
+        .iter()
+        .filter(|(_, c)| (c.unk4, c.unk5) != (default_unk4, default_unk5))
+        .map(|(char_id, c)| format!("{}:{}:{}", char_id, c.unk4, c.unk5))
+        .collect();
+
+    let luminances: BTreeMap<u16, String> = font
+        .chars
+        .iter()
+        .map(|(char_id, c)| (*char_id, glyph_luminance(&c.image)))
+        .collect();
+    let mut luminance_count: BTreeMap<&str, usize> = BTreeMap::new();
+    for luminance in luminances.values() {
+        *luminance_count.entry(luminance).or_default() += 1;
+    }
+    let default_luminance = luminance_count
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(luminance, _)| luminance.to_string())
+        .unwrap_or_else(|| "0".to_string());
+    let luminance_overrides: Vec<String> = luminances
+        .iter()
+        .filter(|(_, luminance)| **luminance != default_luminance)
+        .map(|(char_id, luminance)| format!("{}:{}", char_id, luminance))
+        .collect();
+
+    // the bounding box of every glyph: (width, height, x offset, y offset)
+    let bounding_boxes: Vec<(i32, i32, i32, i32)> = font
+        .chars
+        .values()
+        .map(|c| {
+            let height = c.glyth_height as i32;
+            (
+                c.glyth_width as i32,
+                height,
+                c.xalign as i32,
+                ascent - c.yalign as i32 - height,
+            )
+        })
+        .collect();
+    let min_x = bounding_boxes.iter().map(|b| b.2).min().unwrap_or(0);
+    let min_y = bounding_boxes.iter().map(|b| b.3).min().unwrap_or(0);
+    let max_x = bounding_boxes.iter().map(|b| b.2 + b.0).max().unwrap_or(0);
+    let max_y = bounding_boxes.iter().map(|b| b.3 + b.1).max().unwrap_or(0);
+
+    let mut bdf = String::new();
+    let version = if bits_per_pixel == 1 { "2.1" } else { "2.3" };
+    writeln!(bdf, "STARTFONT {}", version)?;
+    writeln!(bdf, "FONT {}", name)?;
+    if bits_per_pixel == 1 {
+        writeln!(bdf, "SIZE {} 75 75", line_height)?;
+    } else {
+        writeln!(bdf, "SIZE {} 75 75 {}", line_height, bits_per_pixel)?;
+    }
+    writeln!(
+        bdf,
+        "FONTBOUNDINGBOX {} {} {} {}",
+        max_x - min_x,
+        max_y - min_y,
+        min_x,
+        min_y
+    )?;
+    let mut properties = vec![
+        format!("FONT_ASCENT {}", ascent),
+        format!("FONT_DESCENT {}", descent),
+        format!("PMD_DIC_UNK1 {}", font.dic_unk1),
+        format!("PMD_DIC_UNK2 {}", font.dic_unk2),
+        format!("PMD_UNK4 {}", default_unk4),
+        format!("PMD_UNK5 {}", default_unk5),
+        format!("PMD_LUMINANCE \"{}\"", default_luminance),
+    ];
+    if !unk_overrides.is_empty() {
+        properties.push(format!("PMD_UNK_OVERRIDES \"{}\"", unk_overrides.join(" ")));
+    };
+    if !luminance_overrides.is_empty() {
+        properties.push(format!(
+            "PMD_LUMINANCE_OVERRIDES \"{}\"",
+            luminance_overrides.join(" ")
+        ));
+    };
+    writeln!(bdf, "STARTPROPERTIES {}", properties.len())?;
+    for property in properties {
+        writeln!(bdf, "{}", property)?;
+    }
+    writeln!(bdf, "ENDPROPERTIES")?;
+    writeln!(bdf, "CHARS {}", font.chars.len())?;
+    for ((char_id, char_data), bounding_box) in font.chars.iter().zip(bounding_boxes) {
+        writeln!(bdf, "STARTCHAR uni{:04X}", char_id)?;
+        writeln!(bdf, "ENCODING {}", char_id)?;
+        writeln!(
+            bdf,
+            "SWIDTH {} 0",
+            char_data.distance as i32 * 1000 / line_height.max(1)
+        )?;
+        writeln!(bdf, "DWIDTH {} 0", char_data.distance)?;
+        writeln!(
+            bdf,
+            "BBX {} {} {} {}",
+            bounding_box.0, bounding_box.1, bounding_box.2, bounding_box.3
+        )?;
+        writeln!(bdf, "BITMAP")?;
+        let row_bytes = (char_data.glyth_width as usize * bits_per_pixel).div_ceil(8);
+        for row in char_data.image.rows() {
+            let mut bytes = vec![0u8; row_bytes];
+            for (x, pixel) in row.enumerate() {
+                let value = match alpha {
+                    BdfAlpha::Threshold(threshold) => (pixel[3] >= threshold) as u8,
+                    BdfAlpha::Greymap => pixel[3] >> 4,
+                };
+                let bit_index = x * bits_per_pixel;
+                bytes[bit_index / 8] |= value << (8 - bits_per_pixel - bit_index % 8);
+            }
+            for byte in bytes {
+                write!(bdf, "{:02X}", byte)?;
+            }
+            writeln!(bdf)?;
+        }
+        writeln!(bdf, "ENDCHAR")?;
+    }
+    writeln!(bdf, "ENDFONT")?;
+    Ok(bdf)
+}
+
+fn parse_bdf(text: &str) -> Result<ParsedBitmapFont, FontToolError> {
+    let parse_numbers =
+        |values: &[&str], count: usize, keyword: &str| -> Result<Vec<i32>, FontToolError> {
+            let numbers = values
+                .iter()
+                .take(count)
+                .map(|value| value.parse::<i32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid(format!("invalid {} line", keyword)))?;
+            if numbers.len() != count {
+                return Err(invalid(format!("invalid {} line", keyword)));
+            };
+            Ok(numbers)
+        };
+
+    let mut ascent = None;
+    let mut font_bounding_box = None;
+    let mut bits_per_pixel = 1;
+    let mut pmd_properties = BTreeMap::new();
+    let mut glyphs = Vec::new();
+    let mut lines = text.lines();
+    let mut encoding = None;
+    let mut advance = 0;
+    let mut bbx = None;
+    while let Some(line) = lines.next() {
+        let mut parts = line.split_whitespace();
+        let keyword = parts.next().unwrap_or("");
+        let values: Vec<&str> = parts.collect();
+        match keyword {
+            "FONT_ASCENT" => ascent = Some(parse_numbers(&values, 1, keyword)?[0]),
+            // BDF 2.3 greymap fonts have a fourth value
+            "SIZE" if values.len() >= 4 => {
+                bits_per_pixel = parse_numbers(&values, 4, keyword)?[3];
+                if ![1, 2, 4, 8].contains(&bits_per_pixel) {
+                    return Err(invalid(format!(
+                        "unsupported number of bit per pixel: {}",
+                        bits_per_pixel
+                    )));
+                };
+            }
+            "FONTBOUNDINGBOX" => font_bounding_box = Some(parse_numbers(&values, 4, keyword)?),
+            "STARTCHAR" => {
+                encoding = None;
+                advance = 0;
+                bbx = font_bounding_box.clone();
+            }
+            "ENCODING" => {
+                let value = parse_numbers(&values, 1, keyword)?[0];
+                encoding = if value >= 0 { Some(value as u32) } else { None };
+            }
+            "DWIDTH" => advance = parse_numbers(&values, 1, keyword)?[0],
+            "BBX" => bbx = Some(parse_numbers(&values, 4, keyword)?),
+            "BITMAP" => {
+                let bbx = bbx
+                    .clone()
+                    .ok_or_else(|| invalid("a glyph doesn't have a BBX"))?;
+                let (width, height) = (bbx[0].max(0) as u32, bbx[1].max(0) as u32);
+                let row_bytes = (width as usize * bits_per_pixel as usize).div_ceil(8);
+                // the rows are read before creating the image, so its size is checked against the
+                // bitmap actually present in the file
+                let mut rows = Vec::new();
+                for _ in 0..height {
+                    let row = lines
+                        .next()
+                        .ok_or_else(|| invalid("the bitmap of a glyph is truncated"))?
+                        .trim();
+                    if !row.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                        return Err(invalid(format!("invalid bitmap row {:?}", row)));
+                    };
+                    let bytes = row
+                        .as_bytes()
+                        .chunks_exact(2)
+                        .map(|pair| {
+                            let digit = |byte: u8| (byte as char).to_digit(16).unwrap_or(0) as u8;
+                            (digit(pair[0]) << 4) | digit(pair[1])
+                        })
+                        .collect::<Vec<u8>>();
+                    if bytes.len() < row_bytes {
+                        return Err(invalid(format!(
+                            "the bitmap row {:?} is smaller than the BBX of the glyph",
+                            row
+                        )));
+                    };
+                    rows.push(bytes);
+                }
+                let mut image = RgbaImage::new(width, height);
+                for (y, bytes) in rows.iter().enumerate() {
+                    let y = y as u32;
+                    for x in 0..width {
+                        let bit_index = x as usize * bits_per_pixel as usize;
+                        let byte = bytes[bit_index / 8];
+                        let value = (byte << (bit_index % 8)) >> (8 - bits_per_pixel);
+                        let alpha = (value as u32 * 255 / ((1 << bits_per_pixel) - 1)) as u8;
+                        if alpha != 0 {
+                            image.put_pixel(x, y, Rgba([0, 0, 0, alpha]));
+                        }
+                    }
+                }
+                if let Some(encoding) = encoding {
+                    glyphs.push(BitmapGlyph {
+                        encoding,
+                        x_offset: bbx[2],
+                        y_offset: bbx[3],
+                        advance,
+                        image,
+                    });
+                };
+            }
+            _ if keyword.starts_with("PMD_") => {
+                pmd_properties.insert(
+                    keyword.to_string(),
+                    values.join(" ").trim_matches('"').to_string(),
+                );
+            }
+            _ => (),
+        }
+    }
+    let ascent = match (ascent, font_bounding_box) {
+        (Some(ascent), _) => ascent,
+        (None, Some(bounding_box)) => bounding_box[1] + bounding_box[3],
+        (None, None) => return Err(invalid("the font doesn't have a FONT_ASCENT")),
+    };
+    Ok(ParsedBitmapFont {
+        ascent,
+        glyphs,
+        pmd_properties,
+    })
+}
+
+const PCF_PROPERTIES: u32 = 1;
+const PCF_ACCELERATORS: u32 = 1 << 1;
+const PCF_METRICS: u32 = 1 << 2;
+const PCF_BITMAPS: u32 = 1 << 3;
+const PCF_BDF_ENCODINGS: u32 = 1 << 5;
+const PCF_BDF_ACCELERATORS: u32 = 1 << 8;
+
+const PCF_BYTE_MASK: u32 = 1 << 2;
+const PCF_BIT_MASK: u32 = 1 << 3;
+const PCF_COMPRESSED_METRICS: u32 = 0x100;
+
+/// A reader for a table of a PCF file, with the byte order of the table
+struct PcfTable<'a> {
+    data: &'a [u8],
+    format: u32,
+    position: usize,
+}
+
+impl<'a> PcfTable<'a> {
+    fn new(
+        file: &'a [u8],
+        tables: &[(u32, u32, u32)],
+        table_type: u32,
+    ) -> Result<Option<Self>, FontToolError> {
+        let (offset, size) = match tables.iter().find(|table| table.0 == table_type) {
+            Some(table) => (table.1 as usize, table.2 as usize),
+            None => return Ok(None),
+        };
+        let data = file
+            .get(offset..offset + size)
+            .ok_or_else(|| invalid("a table of the PCF file is truncated"))?;
+        // the format is always stored in little endian
+        let format = u32::from_le_bytes(
+            data.get(0..4)
+                .ok_or_else(|| invalid("a table of the PCF file is truncated"))?
+                .try_into()
+                .unwrap(),
+        );
+        Ok(Some(Self {
+            data,
+            format,
+            position: 4,
+        }))
+    }
+
+    fn big_endian(&self) -> bool {
+        self.format & PCF_BYTE_MASK != 0
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], FontToolError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + count)
+            .ok_or_else(|| invalid("a table of the PCF file is truncated"))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, FontToolError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, FontToolError> {
+        let bytes = self.bytes(2)?.try_into().unwrap();
+        Ok(if self.big_endian() {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&mut self) -> Result<u32, FontToolError> {
+        let bytes = self.bytes(4)?.try_into().unwrap();
+        Ok(if self.big_endian() {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+}
+
+/// The metrics of a glyph in a PCF file
+struct PcfMetrics {
+    left_bearing: i32,
+    right_bearing: i32,
+    width: i32,
+    ascent: i32,
+    descent: i32,
+}
+
+fn parse_pcf(data: &[u8]) -> Result<ParsedBitmapFont, FontToolError> {
+    let read_le = |offset: usize| -> Result<u32, FontToolError> {
+        Ok(u32::from_le_bytes(
+            data.get(offset..offset + 4)
+                .ok_or_else(|| invalid("the PCF file is truncated"))?
+                .try_into()
+                .unwrap(),
+        ))
+    };
+    let table_count = read_le(4)? as usize;
+    // (type, offset, size). The counts read from the file aren't trusted to preallocate more than
+    // what the file can contain.
+    let mut tables = Vec::with_capacity(table_count.min(data.len() / 16));
+    for index in 0..table_count {
+        let entry = 8 + index * 16;
+        tables.push((read_le(entry)?, read_le(entry + 12)?, read_le(entry + 8)?));
+    }
+    let missing = |name: &str| invalid(format!("the PCF file doesn't have a {} table", name));
+    let properties = pcf_properties(data, &tables)?;
+
+    // ascent
+    let ascent = match PcfTable::new(data, &tables, PCF_BDF_ACCELERATORS)? {
+        Some(table) => Some(table),
+        None => PcfTable::new(data, &tables, PCF_ACCELERATORS)?,
+    };
+    let ascent = match ascent {
+        Some(mut table) => {
+            table.bytes(8)?;
+            table.u32()? as i32
+        }
+        None => properties
+            .get("FONT_ASCENT")
+            .and_then(|ascent| ascent.parse().ok())
+            .ok_or_else(|| missing("accelerators"))?,
+    };
+
+    // metrics
+    let mut table = PcfTable::new(data, &tables, PCF_METRICS)?.ok_or_else(|| missing("metrics"))?;
+    let mut metrics = Vec::new();
+    if table.format & PCF_COMPRESSED_METRICS != 0 {
+        let count = table.u16()?;
+        for _ in 0..count {
+            let mut value = || -> Result<i32, FontToolError> { Ok(table.u8()? as i32 - 0x80) };
+            metrics.push(PcfMetrics {
+                left_bearing: value()?,
+                right_bearing: value()?,
+                width: value()?,
+                ascent: value()?,
+                descent: value()?,
+            });
+        }
+    } else {
+        let count = table.u32()?;
+        for _ in 0..count {
+            let mut value = || -> Result<i32, FontToolError> { Ok(table.u16()? as i16 as i32) };
+            metrics.push(PcfMetrics {
+                left_bearing: value()?,
+                right_bearing: value()?,
+                width: value()?,
+                ascent: value()?,
+                descent: value()?,
+            });
+            table.u16()?; // attributes
+        }
+    }
+
+    // bitmaps
+    let mut table = PcfTable::new(data, &tables, PCF_BITMAPS)?.ok_or_else(|| missing("bitmaps"))?;
+    let glyph_count = table.u32()? as usize;
+    let mut offsets = Vec::with_capacity(glyph_count.min(data.len() / 4));
+    for _ in 0..glyph_count {
+        offsets.push(table.u32()? as usize);
+    }
+    let pad_index = (table.format & 3) as usize;
+    let mut bitmap_sizes = [0; 4];
+    for size in bitmap_sizes.iter_mut() {
+        *size = table.u32()? as usize;
+    }
+    let bitmap_data = table.bytes(bitmap_sizes[pad_index])?;
+    let row_padding = 1 << pad_index;
+    let scan_unit = 1 << ((table.format >> 4) & 3);
+    let msb_bit_first = table.format & PCF_BIT_MASK != 0;
+    let swap_bytes = table.big_endian() != msb_bit_first && scan_unit > 1;
+
+    let mut images = Vec::with_capacity(offsets.len());
+    for (index, offset) in offsets.iter().enumerate() {
+        let metric = metrics
+            .get(index)
+            .ok_or_else(|| invalid("a glyph of the PCF file doesn't have metrics"))?;
+        let width = (metric.right_bearing - metric.left_bearing).max(0) as u32;
+        let height = (metric.ascent + metric.descent).max(0) as u32;
+        let row_bytes = (width as usize).div_ceil(8).div_ceil(row_padding) * row_padding;
+        let bitmap_end = row_bytes
+            .checked_mul(height as usize)
+            .and_then(|size| size.checked_add(*offset));
+        if bitmap_end.is_none_or(|end| end > bitmap_data.len()) {
+            return Err(invalid("the bitmap of a glyph is truncated"));
+        };
+        let mut image = RgbaImage::new(width, height);
+        for y in 0..height as usize {
+            let start = offset + y * row_bytes;
+            let mut row = bitmap_data
+                .get(start..start + row_bytes)
+                .ok_or_else(|| invalid("the bitmap of a glyph is truncated"))?
+                .to_vec();
+            if swap_bytes {
+                for unit in row.chunks_mut(scan_unit) {
+                    unit.reverse();
+                }
+            };
+            for x in 0..width {
+                let byte = row[(x / 8) as usize];
+                let bit = if msb_bit_first {
+                    byte & (0x80 >> (x % 8))
+                } else {
+                    byte & (1 << (x % 8))
+                };
+                if bit != 0 {
+                    image.put_pixel(x, y as u32, Rgba([0, 0, 0, 255]));
+                }
+            }
+        }
+        images.push(image);
+    }
+
+    // encodings
+    let mut table =
+        PcfTable::new(data, &tables, PCF_BDF_ENCODINGS)?.ok_or_else(|| missing("encodings"))?;
+    let min_byte2 = table.u16()? as u32;
+    let max_byte2 = table.u16()? as u32;
+    let min_byte1 = table.u16()? as u32;
+    let max_byte1 = table.u16()? as u32;
+    table.u16()?; // default char
+    let mut glyphs = Vec::new();
+    for byte1 in min_byte1..=max_byte1 {
+        for byte2 in min_byte2..=max_byte2 {
+            let glyph_index = table.u16()? as usize;
+            if glyph_index == 0xFFFF {
+                continue;
+            };
+            let (metric, image) = match (metrics.get(glyph_index), images.get(glyph_index)) {
+                (Some(metric), Some(image)) => (metric, image),
+                _ => return Err(invalid("an encoding refer to a glyph that doesn't exist")),
+            };
+            glyphs.push(BitmapGlyph {
+                encoding: (byte1 << 8) | byte2,
+                x_offset: metric.left_bearing,
+                y_offset: -metric.descent,
+                advance: metric.width,
+                image: image.clone(),
+            });
+        }
+    }
+    Ok(ParsedBitmapFont {
+        ascent,
+        glyphs,
+        pmd_properties: properties
+            .into_iter()
+            .filter(|(name, _)| name.starts_with("PMD_"))
+            .collect(),
+    })
+}
+
+/// Read the properties of a PCF file, with the integer ones written in decimal
+fn pcf_properties(
+    data: &[u8],
+    tables: &[(u32, u32, u32)],
+) -> Result<BTreeMap<String, String>, FontToolError> {
+    let mut result = BTreeMap::new();
+    let mut table = match PcfTable::new(data, tables, PCF_PROPERTIES)? {
+        Some(table) => table,
+        None => return Ok(result),
+    };
+    let count = table.u32()? as usize;
+    // each property take 9 bytes
+    let mut properties = Vec::with_capacity(count.min(data.len() / 9));
+    for _ in 0..count {
+        let name_offset = table.u32()? as usize;
+        let is_string = table.u8()? != 0;
+        let value = table.u32()?;
+        properties.push((name_offset, is_string, value));
+    }
+    if !count.is_multiple_of(4) {
+        table.bytes(4 - count % 4)?;
+    };
+    let strings_size = table.u32()? as usize;
+    let strings = table.bytes(strings_size)?;
+    let string_at = |offset: usize| {
+        let bytes = strings
+            .get(offset..)
+            .and_then(|string| string.split(|byte| *byte == 0).next())
+            .unwrap_or(&[]);
+        String::from_utf8_lossy(bytes).to_string()
+    };
+    for (name_offset, is_string, value) in properties {
+        let value = if is_string {
+            string_at(value as usize)
+        } else {
+            (value as i32).to_string()
+        };
+        result.insert(string_at(name_offset), value);
+    }
+    Ok(result)
+}
@@ -0,0 +1,424 @@
+use crate::{
+    default_line_height, estimate_baseline, AtlasOptions, CharData, Font, FontToolError,
+    DEFAULT_UNK4, DEFAULT_UNK5,
+};
+use image::{GenericImageView, Rgba, RgbaImage};
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::convert::{TryFrom, TryInto};
+use std::io::Write;
+
+/// A font exported in the AngelCode BMFont format
+pub struct BmFontExport {
+    /// the content of the .fnt file
+    pub fnt: Vec<u8>,
+    /// the only page of the font
+    pub page: RgbaImage,
+}
+
+/// Convert the font to the AngelCode BMFont format, as a text or binary .fnt file.
+///
+/// xalign, yalign and distance are used as xoffset, yoffset and xadvance. The glyphs keep the
+/// layout they have in the .img file, and their colors are scaled from the 0-15 range used by the
+/// game to 0-255. `page_file` is the name of the page image written in the .fnt file.
+pub fn export_bmfont(
+    font: &Font,
+    face: &str,
+    page_file: &str,
+    binary: bool,
+) -> Result<BmFontExport, FontToolError> {
+    let (kand, cte, _) = font.to_game(&AtlasOptions::default())?;
+    let mut page = cte.image.to_rgba8();
+    for pixel in page.pixels_mut() {
+        for channel in 0..3 {
+            pixel[channel] = pixel[channel].saturating_mul(17);
+        }
+    }
+    let line_height = default_line_height(font) as u16;
+    let base = estimate_baseline(font) as u16;
+    let mut fnt = Vec::new();
+    if binary {
+        fnt.extend_from_slice(b"BMF\x03");
+
+        let mut info = Vec::new();
+        info.extend_from_slice(&(line_height as i16).to_le_bytes());
+        info.push(0b0000_0010); // unicode
+        info.push(0); // charset
+        info.extend_from_slice(&100u16.to_le_bytes()); // stretchH
+        info.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]); // aa, padding, spacing, outline
+        info.extend_from_slice(face.as_bytes());
+        info.push(0);
+        write_block(&mut fnt, 1, &info);
+
+        let mut common = Vec::new();
+        for value in &[
+            line_height,
+            base,
+            page.width() as u16,
+            page.height() as u16,
+            1,
+        ] {
+            common.extend_from_slice(&value.to_le_bytes());
+        }
+        common.extend_from_slice(&[0, 0, 0, 0, 0]); // bitField, alpha/red/green/blue channels
+        write_block(&mut fnt, 2, &common);
+
+        let mut pages = page_file.as_bytes().to_vec();
+        pages.push(0);
+        write_block(&mut fnt, 3, &pages);
+
+        let mut chars = Vec::new();
+        for char in &kand.chars {
+            chars.extend_from_slice(&(char.char as u32).to_le_bytes());
+            for value in &[
+                char.start_x,
+                char.start_y,
+                char.glyth_width,
+                char.glyth_height,
+            ] {
+                chars.extend_from_slice(&value.to_le_bytes());
+            }
+            for value in &[char.unk1, char.unk2, char.distance as i16] {
+                chars.extend_from_slice(&value.to_le_bytes());
+            }
+            chars.extend_from_slice(&[0, 15]); // page, chnl
+        }
+        write_block(&mut fnt, 4, &chars);
+    } else {
+        writeln!(
+            fnt,
+            "info face=\"{}\" size={} bold=0 italic=0 charset=\"\" unicode=1 stretchH=100 smooth=1 aa=1 padding=0,0,0,0 spacing=0,0",
+            face.replace('"', ""),
+            line_height
+        )?;
+        writeln!(
+            fnt,
+            "common lineHeight={} base={} scaleW={} scaleH={} pages=1 packed=0",
+            line_height,
+            base,
+            page.width(),
+            page.height()
+        )?;
+        writeln!(fnt, "page id=0 file=\"{}\"", page_file)?;
+        writeln!(fnt, "chars count={}", kand.chars.len())?;
+        for char in &kand.chars {
+            writeln!(
+                fnt,
+                "char id={} x={} y={} width={} height={} xoffset={} yoffset={} xadvance={} page=0 chnl=15",
+                char.char,
+                char.start_x,
+                char.start_y,
+                char.glyth_width,
+                char.glyth_height,
+                char.unk1,
+                char.unk2,
+                char.distance
+            )?;
+        }
+    }
+    Ok(BmFontExport { fnt, page })
+}
+
+fn write_block(output: &mut Vec<u8>, block_type: u8, content: &[u8]) {
+    output.push(block_type);
+    output.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    output.extend_from_slice(content);
+}
+
+struct BmFontChar {
+    id: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    xoffset: i16,
+    yoffset: i16,
+    xadvance: u16,
+    page: u8,
+    chnl: u8,
+}
+
+struct ParsedBmFont {
+    packed: bool,
+    pages: Vec<String>,
+    chars: Vec<BmFontChar>,
+}
+
+/// Split a line of a text .fnt file in its tag and its `key=value` attributes
+fn parse_text_line(line: &str) -> (String, BTreeMap<String, String>) {
+    let mut attributes = BTreeMap::new();
+    let mut chars = line.trim().chars().peekable();
+    let tag: String = chars.by_ref().take_while(|c| !c.is_whitespace()).collect();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let key: String = chars.by_ref().take_while(|c| *c != '=').collect();
+        if key.is_empty() {
+            break;
+        };
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            value.extend(chars.by_ref().take_while(|c| *c != '"'));
+        } else {
+            value.extend(chars.by_ref().take_while(|c| !c.is_whitespace()));
+        }
+        attributes.insert(key.trim().to_string(), value);
+    }
+    (tag, attributes)
+}
+
+/// Convert a number read from a text .fnt file to the type it is stored as
+fn in_range<T: TryFrom<i64>>(key: &str, value: i64) -> Result<T, FontToolError> {
+    T::try_from(value).map_err(|_| {
+        FontToolError::InvalidBmFont(format!("the {} value {} is out of range", key, value))
+    })
+}
+
+fn parse_text(text: &str) -> Result<ParsedBmFont, FontToolError> {
+    let mut result = ParsedBmFont {
+        packed: false,
+        pages: Vec::new(),
+        chars: Vec::new(),
+    };
+    for line in text.lines() {
+        let (tag, attributes) = parse_text_line(line);
+        let get = |key: &str| -> Result<i64, FontToolError> {
+            let value = attributes.get(key).ok_or_else(|| {
+                FontToolError::InvalidBmFont(format!("the {} line has no {} value", tag, key))
+            })?;
+            value.parse().map_err(|_| {
+                FontToolError::InvalidBmFont(format!(
+                    "the {} value {:?} isn't a number",
+                    key, value
+                ))
+            })
+        };
+        match tag.as_str() {
+            "common" => result.packed = attributes.get("packed").map(String::as_str) == Some("1"),
+            "page" => {
+                // the characters store their page in a byte
+                let id: u8 = in_range("id", get("id")?)?;
+                let file = attributes.get("file").cloned().unwrap_or_default();
+                if result.pages.len() <= id as usize {
+                    result.pages.resize(id as usize + 1, String::new());
+                };
+                result.pages[id as usize] = file;
+            }
+            "char" => result.chars.push(BmFontChar {
+                id: in_range("id", get("id")?)?,
+                x: in_range("x", get("x")?)?,
+                y: in_range("y", get("y")?)?,
+                width: in_range("width", get("width")?)?,
+                height: in_range("height", get("height")?)?,
+                xoffset: in_range("xoffset", get("xoffset")?)?,
+                yoffset: in_range("yoffset", get("yoffset")?)?,
+                xadvance: in_range("xadvance", get("xadvance")?)?,
+                page: in_range("page", get("page").unwrap_or(0))?,
+                chnl: in_range("chnl", get("chnl").unwrap_or(15))?,
+            }),
+            _ => (),
+        }
+    }
+    Ok(result)
+}
+
+fn parse_binary(data: &[u8]) -> Result<ParsedBmFont, FontToolError> {
+    let truncated = || FontToolError::InvalidBmFont("the binary .fnt file is truncated".into());
+    let u16_at = |block: &[u8], offset: usize| -> Result<u16, FontToolError> {
+        Ok(u16::from_le_bytes(
+            block
+                .get(offset..offset + 2)
+                .ok_or_else(truncated)?
+                .try_into()
+                .unwrap(),
+        ))
+    };
+    if data.get(3) != Some(&3) {
+        return Err(FontToolError::InvalidBmFont(
+            "only the version 3 of the binary .fnt format is supported".into(),
+        ));
+    };
+    let mut result = ParsedBmFont {
+        packed: false,
+        pages: Vec::new(),
+        chars: Vec::new(),
+    };
+    let mut offset = 4;
+    while offset < data.len() {
+        let block_type = data[offset];
+        let size = u32::from_le_bytes(
+            data.get(offset + 1..offset + 5)
+                .ok_or_else(truncated)?
+                .try_into()
+                .unwrap(),
+        ) as usize;
+        let block = data
+            .get(offset + 5..offset + 5 + size)
+            .ok_or_else(truncated)?;
+        offset += 5 + size;
+        match block_type {
+            // the packed flag is the bit 7 of bitField, after five u16 values
+            2 => result.packed = *block.get(10).ok_or_else(truncated)? & 0x80 != 0,
+            3 => {
+                result.pages = block
+                    .split(|byte| *byte == 0)
+                    .filter(|name| !name.is_empty())
+                    .map(|name| String::from_utf8_lossy(name).to_string())
+                    .collect()
+            }
+            4 => {
+                for char in block.chunks(20) {
+                    if char.len() != 20 {
+                        return Err(truncated());
+                    };
+                    result.chars.push(BmFontChar {
+                        id: u32::from_le_bytes(char[0..4].try_into().unwrap()),
+                        x: u16_at(char, 4)? as u32,
+                        y: u16_at(char, 6)? as u32,
+                        width: u16_at(char, 8)? as u32,
+                        height: u16_at(char, 10)? as u32,
+                        xoffset: u16_at(char, 12)? as i16,
+                        yoffset: u16_at(char, 14)? as i16,
+                        xadvance: u16_at(char, 16)?,
+                        page: char[18],
+                        chnl: char[19],
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(result)
+}
+
+/// Import a font in the AngelCode BMFont format (text or binary .fnt file).
+///
+/// `load_page` is called with the file name of each page used by the font. The xoffset, yoffset
+/// and xadvance of the characters are used as xalign, yalign and distance. The colors are
+/// scaled to the 0-15 range used by the game.
+pub fn import_bmfont<F>(fnt: &[u8], mut load_page: F) -> Result<Font, FontToolError>
+where
+    F: FnMut(&str) -> Result<RgbaImage, FontToolError>,
+{
+    let parsed = if fnt.starts_with(b"BMF") {
+        parse_binary(fnt)?
+    } else {
+        parse_text(&String::from_utf8_lossy(fnt))?
+    };
+    let mut pages = BTreeMap::new();
+    let mut font = Font::default();
+    for char in parsed.chars {
+        let char_id: u16 = char
+            .id
+            .try_into()
+            .map_err(|_| match std::char::from_u32(char.id) {
+                Some(chara) => FontToolError::NonBmpChar(chara),
+                None => FontToolError::InvalidBmFont(format!("invalid character id {}", char.id)),
+            })?;
+        if let Entry::Vacant(entry) = pages.entry(char.page) {
+            let page_file = parsed.pages.get(char.page as usize).ok_or_else(|| {
+                FontToolError::InvalidBmFont(format!("the page {} isn't defined", char.page))
+            })?;
+            entry.insert(load_page(page_file)?);
+        };
+        let page = &pages[&char.page];
+        let fit = |start: u32, size: u32, limit: u32| {
+            start.checked_add(size).is_some_and(|end| end <= limit)
+        };
+        if !fit(char.x, char.width, page.width()) || !fit(char.y, char.height, page.height()) {
+            return Err(FontToolError::GlyphOutOfAtlas(char_id));
+        };
+        let mut image = page
+            .view(char.x, char.y, char.width, char.height)
+            .to_image();
+        for pixel in image.pixels_mut() {
+            *pixel = if parsed.packed && char.chnl != 15 {
+                // the glyph is stored in a single channel
+                let channel = match char.chnl {
+                    1 => 2,
+                    2 => 1,
+                    4 => 0,
+                    _ => 3,
+                };
+                Rgba([15, 15, 15, pixel[channel]])
+            } else {
+                let scale = |value: u8| (value as u16 * 15 / 255) as u8;
+                Rgba([scale(pixel[0]), scale(pixel[1]), scale(pixel[2]), pixel[3]])
+            };
+        }
+        let char_data = CharData::new(
+            char_id,
+            image,
+            char.xoffset,
+            char.yoffset,
+            char.xadvance,
+            DEFAULT_UNK4,
+            DEFAULT_UNK5,
+        )?;
+        font.chars.insert(char_id, char_data);
+    }
+    Ok(font)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A binary .fnt file with a single page and a single character, with the bitField of the
+    /// common block set to `bit_field`
+    fn binary_fnt(bit_field: u8) -> Vec<u8> {
+        let mut fnt = b"BMF\x03".to_vec();
+        let mut common = Vec::new();
+        for value in &[18u16, 14, 16, 16, 1] {
+            common.extend_from_slice(&value.to_le_bytes());
+        }
+        common.extend_from_slice(&[bit_field, 0, 0, 0, 0]);
+        write_block(&mut fnt, 2, &common);
+        write_block(&mut fnt, 3, b"page_0.png\0");
+        let mut chars = Vec::new();
+        chars.extend_from_slice(&65u32.to_le_bytes());
+        for value in &[0u16, 0, 2, 2, 0, 0, 3] {
+            chars.extend_from_slice(&value.to_le_bytes());
+        }
+        chars.extend_from_slice(&[0, 4]);
+        write_block(&mut fnt, 4, &chars);
+        fnt
+    }
+
+    #[test]
+    fn binary_packed_flag() {
+        assert!(parse_binary(&binary_fnt(0x80)).unwrap().packed);
+        assert!(!parse_binary(&binary_fnt(0)).unwrap().packed);
+        // the other flags (like unicode) don't mean the font is packed
+        assert!(!parse_binary(&binary_fnt(0x7F)).unwrap().packed);
+    }
+
+    #[test]
+    fn binary_large_xadvance() {
+        let mut fnt = binary_fnt(0);
+        // the xadvance of the last character
+        let offset = fnt.len() - 4;
+        fnt[offset..offset + 2].copy_from_slice(&40000u16.to_le_bytes());
+        assert_eq!(parse_binary(&fnt).unwrap().chars[0].xadvance, 40000);
+    }
+
+    #[test]
+    fn text_out_of_range_values() {
+        let page = |_: &str| Ok(RgbaImage::new(16, 16));
+        for fnt in &[
+            "page id=-1 file=\"page_0.png\"\n",
+            "page id=0 file=\"page_0.png\"\nchar id=65 x=-1 y=0 width=2 height=2 xoffset=0 yoffset=0 xadvance=3\n",
+            "page id=0 file=\"page_0.png\"\nchar id=65 x=0 y=0 width=2 height=2 xoffset=0 yoffset=0 xadvance=3 chnl=256\n",
+            "page id=4000000000 file=\"page_0.png\"\n",
+            "page id=0 file=\"page_0.png\"\nchar id=65 x=0 y=0 width=2 height=2 xoffset=40000 yoffset=0 xadvance=3\n",
+            "page id=0 file=\"page_0.png\"\nchar id=65 x=0 y=0 width=2 height=2 xoffset=0 yoffset=0 xadvance=70000\n",
+        ] {
+            assert!(matches!(
+                import_bmfont(fnt.as_bytes(), page),
+                Err(FontToolError::InvalidBmFont(_))
+            ));
+        }
+    }
+}
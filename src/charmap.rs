@@ -0,0 +1,82 @@
+use crate::FontToolError;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryInto;
+use std::io::Read;
+
+/// Parse a codepoint written as `U+XXXX`, `0xXXXX`, or as the character itself
+pub fn parse_codepoint(text: &str) -> Option<u32> {
+    if let Some(hex) = text
+        .strip_prefix("U+")
+        .or_else(|| text.strip_prefix("u+"))
+        .or_else(|| text.strip_prefix("0x"))
+    {
+        return u32::from_str_radix(hex, 16).ok();
+    };
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(chara), None) => Some(chara as u32),
+        _ => None,
+    }
+}
+
+/// Read a table associating a character id of the game to a character of the source font.
+///
+/// Each line contain the id used in the game then the source character, separated by spaces
+/// (for example `U+E000 U+1F600`). Empty lines and text after a `#` are ignored.
+pub fn read_char_mapping<R: Read>(reader: &mut R) -> Result<BTreeMap<u16, char>, FontToolError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let mut mapping = BTreeMap::new();
+    for (line_index, line) in text.lines().enumerate() {
+        let content = line.split('#').next().unwrap_or("").trim();
+        if content.is_empty() {
+            continue;
+        };
+        let invalid = || FontToolError::InvalidMappingLine(line_index + 1, line.to_string());
+        let mut parts = content.split_whitespace();
+        let (game, source) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(game), Some(source), None) => (game, source),
+            _ => return Err(invalid()),
+        };
+        let game_id: u16 = parse_codepoint(game)
+            .and_then(|codepoint| codepoint.try_into().ok())
+            .ok_or_else(invalid)?;
+        let source_char = parse_codepoint(source)
+            .and_then(std::char::from_u32)
+            .ok_or_else(invalid)?;
+        mapping.insert(game_id, source_char);
+    }
+    Ok(mapping)
+}
+
+/// Associate each character to include with the id used in the game.
+///
+/// Characters of the Basic Multilingual Plane use their codepoint as id, even when the mapping
+/// also copy them to another id. The other can't be stored in a .dic file, so they should be
+/// present in the mapping (which also add its own characters), or they are either skipped or
+/// rejected with an error depending on `skip_non_bmp`.
+/// The characters skipped are returned in the second value.
+pub fn map_chars(
+    chars: &BTreeSet<char>,
+    mapping: &BTreeMap<u16, char>,
+    skip_non_bmp: bool,
+) -> Result<(BTreeMap<u16, char>, Vec<char>), FontToolError> {
+    let mapped_chars: BTreeSet<char> = mapping.values().cloned().collect();
+    let mut result = BTreeMap::new();
+    let mut skipped = Vec::new();
+    for chara in chars {
+        match (*chara as u32).try_into() {
+            Ok(char_id) => {
+                result.insert(char_id, *chara);
+            }
+            // already stored at the id it is mapped to
+            Err(_) if mapped_chars.contains(chara) => (),
+            Err(_) if skip_non_bmp => skipped.push(*chara),
+            Err(_) => return Err(FontToolError::NonBmpChar(*chara)),
+        }
+    }
+    for (char_id, chara) in mapping {
+        result.insert(*char_id, *chara);
+    }
+    Ok((result, skipped))
+}
@@ -0,0 +1,216 @@
+use crate::{parse_codepoint, read_char_list, FontToolError};
+use encoding_rs::{Encoding, EUC_JP, GBK};
+use pmd_dic::KandFile;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A named set of characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharSetPreset {
+    /// the printable ASCII characters
+    Ascii,
+    /// the printable characters of ISO 8859-1
+    Latin1,
+    /// the Latin Extended-A block
+    LatinExtendedA,
+    /// the Cyrillic block
+    Cyrillic,
+    /// the Greek and Coptic block
+    Greek,
+    /// the non-kanji characters and the level 1 kanji of JIS X 0208
+    JisX0208Level1,
+    /// every character of GB 2312
+    Gb2312,
+}
+
+impl CharSetPreset {
+    pub const ALL: [CharSetPreset; 7] = [
+        Self::Ascii,
+        Self::Latin1,
+        Self::LatinExtendedA,
+        Self::Cyrillic,
+        Self::Greek,
+        Self::JisX0208Level1,
+        Self::Gb2312,
+    ];
+
+    /// The name used to refer to this preset in a char set
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ascii => "ascii",
+            Self::Latin1 => "latin-1",
+            Self::LatinExtendedA => "latin-extended-a",
+            Self::Cyrillic => "cyrillic",
+            Self::Greek => "greek",
+            Self::JisX0208Level1 => "jis-x-0208-level-1",
+            Self::Gb2312 => "gb2312",
+        }
+    }
+
+    /// Return the characters of this preset
+    pub fn chars(self) -> BTreeSet<char> {
+        match self {
+            Self::Ascii => chars_in_range(0x20, 0x7E),
+            Self::Latin1 => {
+                let mut chars = chars_in_range(0x20, 0x7E);
+                chars.extend(chars_in_range(0xA0, 0xFF));
+                chars
+            }
+            Self::LatinExtendedA => chars_in_range(0x100, 0x17F),
+            Self::Cyrillic => chars_in_range(0x400, 0x4FF),
+            Self::Greek => chars_in_range(0x370, 0x3FF)
+                .into_iter()
+                // the unassigned codepoints of the block
+                .filter(
+                    |c| !matches!(*c as u32, 0x378..=0x379 | 0x380..=0x383 | 0x38B | 0x38D | 0x3A2),
+                )
+                .collect(),
+            // row 1 to 8 contain the symbols, kana, Latin, Greek and Cyrillic letters, row 16 to
+            // 47 the level 1 kanji
+            Self::JisX0208Level1 => chars_of_double_byte_charset(
+                EUC_JP,
+                (1..=8)
+                    .chain(16..=47)
+                    .flat_map(|row| (1..=94).map(move |cell| (row, cell))),
+            ),
+            // GB2312 is a subset of GBK, with the same EUC-CN encoding. GBK fill some of the
+            // unassigned cells of GB2312, so they are filtered out.
+            Self::Gb2312 => chars_of_double_byte_charset(
+                GBK,
+                (1..=9)
+                    .chain(16..=87)
+                    .flat_map(|row| (1..=94).map(move |cell| (row, cell)))
+                    .filter(|(row, cell)| is_gb2312_cell(*row, *cell)),
+            ),
+        }
+    }
+}
+
+impl FromStr for CharSetPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        Self::ALL
+            .iter()
+            .find(|preset| preset.name() == s)
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "unknown char set preset {:?} (expected one of {})",
+                    s,
+                    Self::ALL
+                        .iter()
+                        .map(|preset| preset.name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+}
+
+fn chars_in_range(start: u32, end: u32) -> BTreeSet<char> {
+    (start..=end).filter_map(std::char::from_u32).collect()
+}
+
+/// Decode the given (row, cell) pairs of a 94×94 character set encoded with EUC
+fn chars_of_double_byte_charset(
+    encoding: &'static Encoding,
+    cells: impl Iterator<Item = (u8, u8)>,
+) -> BTreeSet<char> {
+    let mut chars = BTreeSet::new();
+    for (row, cell) in cells {
+        let bytes = [row + 0xA0, cell + 0xA0];
+        let (decoded, had_errors) = encoding.decode_without_bom_handling(&bytes);
+        if had_errors {
+            continue;
+        };
+        chars.extend(decoded.chars());
+    }
+    chars
+}
+
+/// Tell if a cell is assigned in GB2312
+fn is_gb2312_cell(row: u8, cell: u8) -> bool {
+    match row {
+        1 | 3 => true,
+        2 => matches!(cell, 17..=66 | 69..=78 | 81..=92),
+        4 => cell <= 83,
+        5 => cell <= 86,
+        6 => matches!(cell, 1..=24 | 33..=56),
+        7 => matches!(cell, 1..=33 | 49..=81),
+        8 => matches!(cell, 1..=26 | 37..=73),
+        9 => matches!(cell, 4..=79),
+        55 => cell <= 89,
+        16..=87 => true,
+        _ => false,
+    }
+}
+
+/// Return the characters of the glyphs of a .dic file
+pub fn read_dic_char_set(path: &Path) -> Result<BTreeSet<char>, FontToolError> {
+    let dic_file =
+        File::open(path).map_err(|err| FontToolError::FileIOError(err, path.to_path_buf()))?;
+    let kand = KandFile::new_from_reader(&mut BufReader::new(dic_file))?;
+    Ok(kand
+        .chars
+        .iter()
+        .filter_map(|kand_char| std::char::from_u32(kand_char.char as u32))
+        .collect())
+}
+
+/// Parse a char set, made of items separated by commas. Each item can be:
+/// - the path to a .dic file, to take every character of this font
+/// - the path to a UTF-8 text file, to take every character it contains
+/// - a preset name (see [`CharSetPreset`])
+/// - a codepoint (`U+00E9`) or an inclusive range of codepoint (`U+0020-U+007E`)
+///
+/// If the whole text is the path of an existing file, it is read as a single item.
+pub fn parse_char_set(text: &str) -> Result<BTreeSet<char>, FontToolError> {
+    if Path::new(text).is_file() {
+        return read_char_set_file(Path::new(text));
+    };
+    let mut chars = BTreeSet::new();
+    for item in text.split(',') {
+        let item = item.trim();
+        if Path::new(item).is_file() {
+            chars.extend(read_char_set_file(Path::new(item))?);
+        } else if let Ok(preset) = CharSetPreset::from_str(item) {
+            chars.extend(preset.chars());
+        } else if let Some((start, end)) = parse_range(item) {
+            chars.extend(chars_in_range(start, end));
+        } else {
+            return Err(FontToolError::InvalidCharSet(item.to_string()));
+        };
+    }
+    Ok(chars)
+}
+
+fn parse_range(item: &str) -> Option<(u32, u32)> {
+    let (start, end) = match item.split_once('-') {
+        Some((start, end)) if !start.is_empty() => (start, end),
+        _ => (item, item),
+    };
+    let start = parse_codepoint(start.trim())?;
+    let end = parse_codepoint(end.trim())?;
+    if start > end {
+        return None;
+    };
+    Some((start, end))
+}
+
+fn read_char_set_file(path: &Path) -> Result<BTreeSet<char>, FontToolError> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("dic"))
+    {
+        read_dic_char_set(path)
+    } else {
+        let mut file =
+            File::open(path).map_err(|err| FontToolError::FileIOError(err, path.to_path_buf()))?;
+        read_char_list(&mut file)
+    }
+}
@@ -0,0 +1,215 @@
+use crate::{CharData, Font};
+use image::{Rgba, RgbaImage};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// A difference between the glyphs of two fonts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GlyphDifference {
+    /// the character is only present in the first font
+    Removed(u16),
+    /// the character is only present in the second font
+    Added(u16),
+    /// a metric of the character changed
+    Metric {
+        char_id: u16,
+        metric: &'static str,
+        old: i32,
+        new: i32,
+    },
+    /// the glyph have the same size in both font, but some pixel are different
+    Pixels { char_id: u16, changed_pixels: usize },
+}
+
+impl GlyphDifference {
+    pub fn char_id(&self) -> u16 {
+        match self {
+            Self::Removed(char_id) | Self::Added(char_id) => *char_id,
+            Self::Metric { char_id, .. } | Self::Pixels { char_id, .. } => *char_id,
+        }
+    }
+}
+
+/// Describe a character id, with the character itself when it is printable
+pub fn display_char(char_id: u16) -> String {
+    match std::char::from_u32(char_id as u32).filter(|c| !c.is_control()) {
+        Some(chara) => format!("{} ({:?})", char_id, chara),
+        None => char_id.to_string(),
+    }
+}
+
+impl fmt::Display for GlyphDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Removed(char_id) => write!(f, "{}: removed", display_char(*char_id)),
+            Self::Added(char_id) => write!(f, "{}: added", display_char(*char_id)),
+            Self::Metric {
+                char_id,
+                metric,
+                old,
+                new,
+            } => write!(
+                f,
+                "{}: {} changed from {} to {}",
+                display_char(*char_id),
+                metric,
+                old,
+                new
+            ),
+            Self::Pixels {
+                char_id,
+                changed_pixels,
+            } => write!(
+                f,
+                "{}: {} pixels changed",
+                display_char(*char_id),
+                changed_pixels
+            ),
+        }
+    }
+}
+
+fn compare_char(char_id: u16, old: &CharData, new: &CharData, result: &mut Vec<GlyphDifference>) {
+    let metrics = [
+        ("width", old.glyth_width as i32, new.glyth_width as i32),
+        ("height", old.glyth_height as i32, new.glyth_height as i32),
+        ("xalign", old.xalign as i32, new.xalign as i32),
+        ("yalign", old.yalign as i32, new.yalign as i32),
+        ("distance", old.distance as i32, new.distance as i32),
+        ("unk4", old.unk4 as i32, new.unk4 as i32),
+        ("unk5", old.unk5 as i32, new.unk5 as i32),
+    ];
+    for (metric, old, new) in metrics.iter() {
+        if old != new {
+            result.push(GlyphDifference::Metric {
+                char_id,
+                metric,
+                old: *old,
+                new: *new,
+            });
+        }
+    }
+    if old.image.dimensions() == new.image.dimensions() {
+        let changed_pixels = old
+            .image
+            .pixels()
+            .zip(new.image.pixels())
+            .filter(|(old_pixel, new_pixel)| old_pixel != new_pixel)
+            .count();
+        if changed_pixels != 0 {
+            result.push(GlyphDifference::Pixels {
+                char_id,
+                changed_pixels,
+            });
+        }
+    }
+}
+
+/// List every difference between the glyphs of two fonts, sorted by character id
+pub fn compare_fonts(old: &Font, new: &Font) -> Vec<GlyphDifference> {
+    let mut result = Vec::new();
+    for (char_id, old_char) in &old.chars {
+        match new.chars.get(char_id) {
+            Some(new_char) => compare_char(*char_id, old_char, new_char, &mut result),
+            None => result.push(GlyphDifference::Removed(*char_id)),
+        }
+    }
+    for char_id in new.chars.keys() {
+        if !old.chars.contains_key(char_id) {
+            result.push(GlyphDifference::Added(*char_id));
+        }
+    }
+    result.sort_by_key(|difference| difference.char_id());
+    result
+}
+
+const DIFF_BACKGROUND: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const DIFF_SEPARATOR: Rgba<u8> = Rgba([64, 64, 64, 255]);
+const DIFF_PEN: Rgba<u8> = Rgba([0, 0, 112, 255]);
+
+/// Draw the glyphs of the characters that differ between two fonts, one row per character. Each
+/// row contain the old glyph, the new glyph, and both glyphs overlaid, with the pixels only in the
+/// old glyph in red and the ones only in the new glyph in green. The pen position before and after
+/// the glyph is marked with a blue line.
+pub fn render_differences(old: &Font, new: &Font, differences: &[GlyphDifference]) -> RgbaImage {
+    let char_ids: BTreeSet<u16> = differences.iter().map(|d| d.char_id()).collect();
+
+    // the bounds of every glyph and their advance, relative to the pen position
+    let (mut left, mut top, mut right, mut bottom) = (0, 0, 1, 1);
+    for char_id in &char_ids {
+        for char_data in old
+            .chars
+            .get(char_id)
+            .iter()
+            .chain(new.chars.get(char_id).iter())
+        {
+            left = left.min(char_data.xalign as i32);
+            top = top.min(char_data.yalign as i32);
+            right = right
+                .max(char_data.xalign as i32 + char_data.glyth_width as i32)
+                .max(char_data.distance as i32 + 1);
+            bottom = bottom.max(char_data.yalign as i32 + char_data.glyth_height as i32);
+        }
+    }
+    let cell_width = (right - left) as u32;
+    let cell_height = (bottom - top) as u32;
+
+    let mut image = RgbaImage::from_pixel(
+        cell_width * 3 + 4,
+        (cell_height + 1) * char_ids.len() as u32 + 1,
+        DIFF_SEPARATOR,
+    );
+    let alpha_at = |char_data: Option<&CharData>, x: i32, y: i32| -> u8 {
+        let char_data = match char_data {
+            Some(char_data) => char_data,
+            None => return 0,
+        };
+        let glyph_x = x - char_data.xalign as i32;
+        let glyph_y = y - char_data.yalign as i32;
+        if glyph_x < 0
+            || glyph_y < 0
+            || glyph_x >= char_data.glyth_width as i32
+            || glyph_y >= char_data.glyth_height as i32
+        {
+            return 0;
+        };
+        char_data.image.get_pixel(glyph_x as u32, glyph_y as u32)[3]
+    };
+    for (row, char_id) in char_ids.iter().enumerate() {
+        let old_char = old.chars.get(char_id);
+        let new_char = new.chars.get(char_id);
+        let cell_y = row as u32 * (cell_height + 1) + 1;
+        for y in 0..cell_height {
+            for x in 0..cell_width {
+                let pen_x = x as i32 + left;
+                let pen_y = y as i32 + top;
+                let old_alpha = alpha_at(old_char, pen_x, pen_y);
+                let new_alpha = alpha_at(new_char, pen_x, pen_y);
+                let is_pen = |char_data: Option<&CharData>| {
+                    char_data.is_some_and(|c| pen_x == 0 || pen_x == c.distance as i32)
+                };
+                let glyph_color = |alpha: u8, char_data: Option<&CharData>| {
+                    if alpha == 0 && is_pen(char_data) {
+                        DIFF_PEN
+                    } else if alpha == 0 {
+                        DIFF_BACKGROUND
+                    } else {
+                        Rgba([alpha, alpha, alpha, 255])
+                    }
+                };
+                image.put_pixel(1 + x, cell_y + y, glyph_color(old_alpha, old_char));
+                image.put_pixel(
+                    2 + cell_width + x,
+                    cell_y + y,
+                    glyph_color(new_alpha, new_char),
+                );
+                image.put_pixel(
+                    3 + cell_width * 2 + x,
+                    cell_y + y,
+                    Rgba([old_alpha, new_alpha, old_alpha.min(new_alpha), 255]),
+                );
+            }
+        }
+    }
+    image
+}
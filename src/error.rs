@@ -0,0 +1,74 @@
+use image::ImageError;
+use pmd_cte::{CteDecodeError, CteEncodeError};
+use std::io;
+use std::path::PathBuf;
+use std::string::FromUtf8Error;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum FontToolError {
+    #[error("an input/output error occured")]
+    IOError(#[from] io::Error),
+    #[error("can't format the output")]
+    FormatError(#[from] std::fmt::Error),
+    #[error("can't read the .dic file")]
+    DicReadError(#[from] binread::Error),
+    #[error("can't write the .dic file")]
+    DicWriteError(#[source] io::Error),
+    #[error("can't read the .img file")]
+    ImgReadError(#[from] CteDecodeError),
+    #[error("can't write the .img file")]
+    ImgWriteError(#[from] CteEncodeError),
+    #[error("can't access the file or directory at {1:?}")]
+    FileIOError(#[source] io::Error, PathBuf),
+    #[error("can't read the image at {1:?}")]
+    ImageReadError(#[source] ImageError, PathBuf),
+    #[error("can't write the image at {1:?}")]
+    ImageWriteError(#[source] ImageError, PathBuf),
+    #[error("the glyph of the character {0} isn't contained in the atlas")]
+    GlyphOutOfAtlas(u16),
+    #[error("the glyph of the character {0} is too big ({1}x{2})")]
+    GlyphTooBig(u16, u32, u32),
+    #[error("the glyphs doesn't fit in an atlas addressable by the .dic file")]
+    AtlasTooBig,
+    #[error("the path {0:?} doesn't have the good format of \"charid_unk1_unk2_distance_unk4_unk5.ext\"")]
+    InvalidGlyphFileName(PathBuf),
+    #[error("the file {0:?} represent a character that is also used by another file")]
+    DuplicateChar(PathBuf),
+    #[error("can't read the manifest at {1:?}")]
+    ManifestReadError(#[source] serde_json::Error, PathBuf),
+    #[error("can't write the manifest at {1:?}")]
+    ManifestWriteError(#[source] serde_json::Error, PathBuf),
+    #[error("the char list isn't a valid UTF-8 text")]
+    CharListNotUtf8(#[from] FromUtf8Error),
+    #[error("the character {0:?} is outside of the Basic Multilingual Plane, and can't be stored in a .dic file. It should be mapped to another codepoint.")]
+    NonBmpChar(char),
+    #[error("the line {0} of the char mapping is invalid (expected \"<game codepoint> <source character>\"): {1:?}")]
+    InvalidMappingLine(usize, String),
+    #[error("{0:?} isn't an existing file, a char set preset or a codepoint range (like \"U+0020-U+007E\")")]
+    InvalidCharSet(String),
+    #[error("the line {0} of the unk override file is invalid (expected \"<codepoint> <unk4> <unk5>\"): {1:?}")]
+    InvalidUnkOverrideLine(usize, String),
+    #[error("invalid message file: {0}")]
+    InvalidMessageFile(String),
+    #[error("the base {0} file isn't the one the patch was made for (expected a CRC32 of {1:08x}, found {2:08x})")]
+    PatchBaseMismatch(&'static str, u32, u32),
+    #[error("invalid patch: {0}")]
+    InvalidPatch(String),
+    #[error("invalid text font at line {0}: {1}")]
+    InvalidTextFont(usize, String),
+    #[error("invalid grid sheet: {0}")]
+    InvalidSheet(String),
+    #[error("invalid BMFont file: {0}")]
+    InvalidBmFont(String),
+    #[error("invalid bitmap font: {0}")]
+    InvalidBitmapFont(String),
+    #[error("can't parse the TrueType font: {0}")]
+    TrueTypeParseError(&'static str),
+    #[error("the TrueType font doesn't contain the characters {0:?}")]
+    MissingGlyphs(String),
+    #[error("can't convert the font to TrueType: {0}")]
+    TrueTypeExportError(&'static str),
+    #[error("can't convert the font to TrueType: the distance of the character {0} ({1}) is too big for a TrueType advance")]
+    TrueTypeAdvanceTooBig(u16, u16),
+}
@@ -0,0 +1,266 @@
+use crate::{AtlasLayout, CharData, Font, FontToolError, GlyphPosition, ImgFormat};
+use image::{DynamicImage, ImageOutputFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{create_dir_all, read_dir, write, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The name of the file storing the metrics of every glyph of a folder
+pub const MANIFEST_FILE_NAME: &str = "font.json";
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    /// the unk1 value of the .dic header
+    #[serde(default)]
+    dic_unk1: u32,
+    /// the unk2 value of the .dic header
+    #[serde(default)]
+    dic_unk2: u32,
+    /// the pixel format of the .img file this folder was generated from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    img_format: Option<ImgFormat>,
+    /// the size of the atlas of the .img file this folder was generated from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    atlas_width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    atlas_height: Option<u32>,
+    /// the glyphs, in the order they are written in the .dic file
+    chars: Vec<ManifestChar>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestChar {
+    char: u16,
+    xalign: i16,
+    yalign: i16,
+    distance: u16,
+    unk4: u16,
+    unk5: u16,
+    /// path of the glyph image, relative to the folder
+    image: PathBuf,
+    /// position of the glyph in the atlas of the original .img file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    x: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    y: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+}
+
+fn read_image(path: &Path) -> Result<RgbaImage, FontToolError> {
+    Ok(image::open(path)
+        .map_err(|err| FontToolError::ImageReadError(err, path.to_path_buf()))?
+        .to_rgba8())
+}
+
+/// Read a folder in the format written by [`write_folder`]
+///
+/// If the folder doesn't contain a manifest, it is read with the legacy layout, where each
+/// glyph is stored in a file named `charid_unk1_unk2_distance_unk4_unk5.png`
+pub fn read_folder(input: &Path) -> Result<Font, FontToolError> {
+    let manifest_path = input.join(MANIFEST_FILE_NAME);
+    if manifest_path.exists() {
+        read_folder_manifest(input, &manifest_path)
+    } else {
+        read_folder_legacy(input)
+    }
+}
+
+fn read_folder_manifest(input: &Path, manifest_path: &Path) -> Result<Font, FontToolError> {
+    let manifest_file = File::open(manifest_path)
+        .map_err(|err| FontToolError::FileIOError(err, manifest_path.to_path_buf()))?;
+    let manifest: Manifest = serde_json::from_reader(BufReader::new(manifest_file))
+        .map_err(|err| FontToolError::ManifestReadError(err, manifest_path.to_path_buf()))?;
+    font_from_manifest(manifest, input, read_image)
+}
+
+/// Build the font described by a manifest, `load_image` reading the image of each glyph from
+/// its path in the folder `input`
+fn font_from_manifest<F>(
+    manifest: Manifest,
+    input: &Path,
+    mut load_image: F,
+) -> Result<Font, FontToolError>
+where
+    F: FnMut(&Path) -> Result<RgbaImage, FontToolError>,
+{
+    let mut font = Font {
+        dic_unk1: manifest.dic_unk1,
+        dic_unk2: manifest.dic_unk2,
+        img_format: manifest.img_format.unwrap_or_default(),
+        ..Font::default()
+    };
+    let mut layout = match (manifest.atlas_width, manifest.atlas_height) {
+        (Some(width), Some(height)) => Some(AtlasLayout {
+            width,
+            height,
+            glyphs: Vec::new(),
+        }),
+        _ => None,
+    };
+    for entry in manifest.chars {
+        if let Some(layout) = &mut layout {
+            if let (Some(x), Some(y)) = (entry.x, entry.y) {
+                layout.glyphs.push(GlyphPosition {
+                    char_id: entry.char,
+                    x,
+                    y,
+                });
+            }
+        };
+        let char_path = input.join(&entry.image);
+        let char_image = load_image(&char_path)?;
+        let char_data = CharData::new(
+            entry.char,
+            char_image,
+            entry.xalign,
+            entry.yalign,
+            entry.distance,
+            entry.unk4,
+            entry.unk5,
+        )?;
+        if font.chars.insert(entry.char, char_data).is_some() {
+            return Err(FontToolError::DuplicateChar(char_path));
+        }
+    }
+    font.original_layout = layout;
+    Ok(font)
+}
+
+fn read_folder_legacy(input: &Path) -> Result<Font, FontToolError> {
+    let mut font = Font::default();
+    for char_file_maybe in
+        read_dir(input).map_err(|err| FontToolError::FileIOError(err, input.to_path_buf()))?
+    {
+        let char_file =
+            char_file_maybe.map_err(|err| FontToolError::FileIOError(err, input.to_path_buf()))?;
+        let char_path = char_file.path();
+        let invalid_name = || FontToolError::InvalidGlyphFileName(char_path.clone());
+        let stem = char_path
+            .file_stem()
+            .ok_or_else(invalid_name)?
+            .to_string_lossy();
+        let mut splited = stem.split('_');
+
+        fn read_from_splited<'a, T: FromStr>(
+            iter: &mut impl Iterator<Item = &'a str>,
+        ) -> Option<T> {
+            iter.next().and_then(|text| T::from_str(text).ok())
+        }
+
+        let char_id: u16 = read_from_splited(&mut splited).ok_or_else(invalid_name)?;
+        let xalign = read_from_splited(&mut splited).ok_or_else(invalid_name)?;
+        let yalign = read_from_splited(&mut splited).ok_or_else(invalid_name)?;
+        let distance = read_from_splited(&mut splited).ok_or_else(invalid_name)?;
+        let unk4 = read_from_splited(&mut splited).ok_or_else(invalid_name)?;
+        let unk5 = read_from_splited(&mut splited).ok_or_else(invalid_name)?;
+
+        let char_image = read_image(&char_path)?;
+        let char_data = CharData::new(char_id, char_image, xalign, yalign, distance, unk4, unk5)?;
+        if font.chars.insert(char_id, char_data).is_some() {
+            return Err(FontToolError::DuplicateChar(char_path));
+        }
+    }
+    Ok(font)
+}
+
+/// The character represented by a glyph, to help finding it in the editable formats
+pub(crate) fn char_comment(char_id: u16) -> Option<String> {
+    // the private use area is used for game-specific symbols
+    std::char::from_u32(char_id as u32)
+        .filter(|c| !c.is_control() && !('\u{E000}'..='\u{F8FF}').contains(c))
+        .map(|c| c.to_string())
+}
+
+/// List the glyphs in the order of the original .dic file, with their position in the original
+/// atlas. The glyphs that weren't in it are put at the end.
+pub(crate) fn glyph_order(font: &Font) -> Vec<(u16, Option<(u16, u16)>)> {
+    let mut ordered_chars = Vec::new();
+    let mut written_chars = BTreeSet::new();
+    if let Some(layout) = &font.original_layout {
+        for glyph in &layout.glyphs {
+            if font.chars.contains_key(&glyph.char_id) && written_chars.insert(glyph.char_id) {
+                ordered_chars.push((glyph.char_id, Some((glyph.x, glyph.y))));
+            }
+        }
+    }
+    for char_id in font.chars.keys() {
+        if !written_chars.contains(char_id) {
+            ordered_chars.push((*char_id, None));
+        }
+    }
+    ordered_chars
+}
+
+/// Write every glyph of the font as a separate png file in the output folder, with their
+/// metrics stored in a manifest
+pub fn write_folder(font: &Font, output: &Path) -> Result<(), FontToolError> {
+    create_dir_all(output).map_err(|err| FontToolError::FileIOError(err, output.to_path_buf()))?;
+    for (path, data) in folder_files(font)? {
+        let target_file = output.join(path);
+        write(&target_file, data).map_err(|err| FontToolError::FileIOError(err, target_file))?;
+    }
+    Ok(())
+}
+
+/// The content of each file of a folder written by [`write_folder`], by path relative to the
+/// folder
+pub(crate) fn folder_files(font: &Font) -> Result<BTreeMap<PathBuf, Vec<u8>>, FontToolError> {
+    let mut files = BTreeMap::new();
+    let mut manifest = Manifest {
+        dic_unk1: font.dic_unk1,
+        dic_unk2: font.dic_unk2,
+        img_format: Some(font.img_format),
+        atlas_width: font.original_layout.as_ref().map(|l| l.width),
+        atlas_height: font.original_layout.as_ref().map(|l| l.height),
+        chars: Vec::new(),
+    };
+    for (char_id, position) in glyph_order(font) {
+        let char = &font.chars[&char_id];
+        let image = PathBuf::from(format!("{}.png", char_id));
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(char.image.clone())
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .map_err(|err| FontToolError::ImageWriteError(err, image.clone()))?;
+        files.insert(image.clone(), png);
+        manifest.chars.push(ManifestChar {
+            char: char_id,
+            xalign: char.xalign,
+            yalign: char.yalign,
+            distance: char.distance,
+            unk4: char.unk4,
+            unk5: char.unk5,
+            image,
+            x: position.map(|p| p.0),
+            y: position.map(|p| p.1),
+            comment: char_comment(char_id),
+        });
+    }
+    let manifest_data = serde_json::to_vec_pretty(&manifest)
+        .map_err(|err| FontToolError::ManifestWriteError(err, MANIFEST_FILE_NAME.into()))?;
+    files.insert(MANIFEST_FILE_NAME.into(), manifest_data);
+    Ok(files)
+}
+
+/// Read a font from the files returned by [`folder_files`]
+pub(crate) fn font_from_folder_files(
+    files: &BTreeMap<PathBuf, Vec<u8>>,
+) -> Result<Font, FontToolError> {
+    let missing = |path: &Path| {
+        FontToolError::FileIOError(io::ErrorKind::NotFound.into(), path.to_path_buf())
+    };
+    let manifest_path = Path::new(MANIFEST_FILE_NAME);
+    let manifest_data = files
+        .get(manifest_path)
+        .ok_or_else(|| missing(manifest_path))?;
+    let manifest: Manifest = serde_json::from_slice(manifest_data)
+        .map_err(|err| FontToolError::ManifestReadError(err, manifest_path.to_path_buf()))?;
+    font_from_manifest(manifest, Path::new(""), |char_path| {
+        let png = files.get(char_path).ok_or_else(|| missing(char_path))?;
+        Ok(image::load_from_memory(png)
+            .map_err(|err| FontToolError::ImageReadError(err, char_path.to_path_buf()))?
+            .to_rgba8())
+    })
+}
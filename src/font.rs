@@ -0,0 +1,199 @@
+use crate::atlas::pack;
+use crate::{AtlasLayout, AtlasOptions, AtlasReport, FontToolError, GlyphPosition, ImgFormat};
+use image::{DynamicImage, GenericImage, GenericImageView, ImageBuffer, Rgba};
+use pmd_cte::CteImage;
+use pmd_dic::{KandChar, KandFile};
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::io::{Read, Seek, Write};
+
+/// A single glyph of a [`Font`], with the metrics stored in the .dic file
+#[derive(Clone, PartialEq)]
+pub struct CharData {
+    pub glyth_width: u16,
+    pub glyth_height: u16,
+    pub xalign: i16,
+    pub yalign: i16,
+    pub distance: u16,
+    pub unk4: u16,
+    pub unk5: u16,
+    pub image: ImageBuffer<Rgba<u8>, Vec<u8>>,
+}
+
+impl CharData {
+    /// Create a new glyph from its image, with width and height taken from the image
+    pub fn new(
+        char_id: u16,
+        image: ImageBuffer<Rgba<u8>, Vec<u8>>,
+        xalign: i16,
+        yalign: i16,
+        distance: u16,
+        unk4: u16,
+        unk5: u16,
+    ) -> Result<Self, FontToolError> {
+        let too_big = || FontToolError::GlyphTooBig(char_id, image.width(), image.height());
+        let glyth_width = image.width().try_into().map_err(|_| too_big())?;
+        let glyth_height = image.height().try_into().map_err(|_| too_big())?;
+        Ok(Self {
+            glyth_width,
+            glyth_height,
+            xalign,
+            yalign,
+            distance,
+            unk4,
+            unk5,
+            image,
+        })
+    }
+}
+
+/// A font, as a list of glyph indexed by their character id
+#[derive(Default, Clone)]
+pub struct Font {
+    /// the unk1 value of the .dic header
+    pub dic_unk1: u32,
+    /// the unk2 value of the .dic header
+    pub dic_unk2: u32,
+    /// the pixel format used to store the .img atlas
+    pub img_format: ImgFormat,
+    /// the placement of the glyphs in the .img file this font was read from
+    pub original_layout: Option<AtlasLayout>,
+    pub chars: BTreeMap<u16, CharData>,
+}
+
+impl Font {
+    /// Read a font from a .dic and a .img file
+    pub fn load<D: Read + Seek, I: Read>(dic: &mut D, img: &mut I) -> Result<Self, FontToolError> {
+        let kand = KandFile::new_from_reader(dic)?;
+        let cte = CteImage::decode_cte(img)?;
+        Self::from_game(kand, cte)
+    }
+
+    /// Create a font from the content of a .dic file and its associated .img atlas
+    pub fn from_game(kand: KandFile, cte: CteImage) -> Result<Self, FontToolError> {
+        let atlas = cte.image.to_rgba8();
+        let mut chars = BTreeMap::new();
+        let mut layout = AtlasLayout {
+            width: atlas.width(),
+            height: atlas.height(),
+            glyphs: Vec::with_capacity(kand.chars.len()),
+        };
+        for char in kand.chars {
+            if char.start_x as u32 + char.glyth_width as u32 > atlas.width()
+                || char.start_y as u32 + char.glyth_height as u32 > atlas.height()
+            {
+                return Err(FontToolError::GlyphOutOfAtlas(char.char));
+            };
+            let image = atlas
+                .view(
+                    char.start_x as u32,
+                    char.start_y as u32,
+                    char.glyth_width as u32,
+                    char.glyth_height as u32,
+                )
+                .to_image();
+            layout.glyphs.push(GlyphPosition {
+                char_id: char.char,
+                x: char.start_x,
+                y: char.start_y,
+            });
+            chars.insert(
+                char.char,
+                CharData {
+                    glyth_width: char.glyth_width,
+                    glyth_height: char.glyth_height,
+                    xalign: char.unk1,
+                    yalign: char.unk2,
+                    distance: char.distance,
+                    unk4: char.unk4,
+                    unk5: char.unk5,
+                    image,
+                },
+            );
+        }
+        Ok(Self {
+            dic_unk1: kand.unk1,
+            dic_unk2: kand.unk2,
+            img_format: ImgFormat::from(&cte.original_format),
+            original_layout: Some(layout),
+            chars,
+        })
+    }
+
+    /// Place every glyph in an atlas, and return the content of the .dic and .img files
+    pub fn to_game(
+        &self,
+        options: &AtlasOptions,
+    ) -> Result<(KandFile, CteImage, AtlasReport), FontToolError> {
+        let (layout, original_layout) = match &self.original_layout {
+            Some(layout) if options.keep_original_layout && layout.fits(&self.chars) => {
+                (layout.clone(), true)
+            }
+            _ => {
+                let sizes = self
+                    .chars
+                    .iter()
+                    .map(|(char_id, char_data)| {
+                        (*char_id, (char_data.glyth_width, char_data.glyth_height))
+                    })
+                    .collect();
+                (pack(&sizes, options)?, false)
+            }
+        };
+
+        let mut atlas: ImageBuffer<Rgba<u8>, Vec<u8>> =
+            ImageBuffer::new(layout.width, layout.height);
+        let mut chars = Vec::new();
+        let mut used_pixels = 0;
+        for glyph in &layout.glyphs {
+            let char_data = &self.chars[&glyph.char_id];
+            atlas
+                .copy_from(&char_data.image, glyph.x as u32, glyph.y as u32)
+                .map_err(|_| FontToolError::GlyphOutOfAtlas(glyph.char_id))?;
+            used_pixels += char_data.glyth_width as u64 * char_data.glyth_height as u64;
+            chars.push(KandChar {
+                char: glyph.char_id,
+                start_x: glyph.x,
+                start_y: glyph.y,
+                glyth_width: char_data.glyth_width,
+                glyth_height: char_data.glyth_height,
+                unk1: char_data.xalign,
+                unk2: char_data.yalign,
+                distance: char_data.distance,
+                unk4: char_data.unk4,
+                unk5: char_data.unk5,
+            });
+        }
+
+        let kand_file = KandFile {
+            unk1: self.dic_unk1,
+            unk2: self.dic_unk2,
+            chars,
+        };
+        self.img_format.convert(&mut atlas);
+        let cte_image = CteImage {
+            original_format: self.img_format.to_cte_format(),
+            image: DynamicImage::ImageRgba8(atlas),
+        };
+        let report = AtlasReport {
+            width: layout.width,
+            height: layout.height,
+            used_pixels,
+            original_layout,
+        };
+        Ok((kand_file, cte_image, report))
+    }
+
+    /// Write the font to a .dic and a .img file
+    pub fn save<D: Write, I: Write>(
+        &self,
+        dic: &mut D,
+        img: &mut I,
+        options: &AtlasOptions,
+    ) -> Result<AtlasReport, FontToolError> {
+        let (kand_file, cte_image, report) = self.to_game(options)?;
+        kand_file.write(dic).map_err(FontToolError::DicWriteError)?;
+        cte_image.encode_cte(img)?;
+        Ok(report)
+    }
+}
@@ -0,0 +1,63 @@
+use image::{Rgba, RgbaImage};
+use pmd_cte::CteFormat;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// The pixel format of a .img file. This contain every format supported by `pmd_cte`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImgFormat {
+    /// 4 bit of luminance and 4 bit of alpha. The luminance is stored as-is in the decoded
+    /// image (between 0 and 15).
+    #[default]
+    A8,
+}
+
+impl FromStr for ImgFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "a8" => Self::A8,
+            _ => return Err(format!("unsupported .img format {:?} (supported: a8)", s)),
+        })
+    }
+}
+
+impl fmt::Display for ImgFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::A8 => write!(f, "a8"),
+        }
+    }
+}
+
+impl From<&CteFormat> for ImgFormat {
+    fn from(format: &CteFormat) -> Self {
+        match format {
+            CteFormat::A8 => Self::A8,
+        }
+    }
+}
+
+impl ImgFormat {
+    pub fn to_cte_format(self) -> CteFormat {
+        match self {
+            Self::A8 => CteFormat::A8,
+        }
+    }
+
+    /// Convert the atlas so every pixel can be encoded in this format without overflowing
+    pub fn convert(self, image: &mut RgbaImage) {
+        match self {
+            Self::A8 => {
+                for pixel in image.pixels_mut() {
+                    let luminance =
+                        ((pixel[0] as u16 + pixel[1] as u16 + pixel[2] as u16) / 3).min(15) as u8;
+                    *pixel = Rgba([luminance, luminance, luminance, pixel[3] & 0xF0]);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! A library to read, edit and write the fonts of pokemon super mystery dungeon (stored as a .dic and a .img file)
+
+mod error;
+pub use error::FontToolError;
+
+mod atlas;
+pub use atlas::{AtlasLayout, AtlasOptions, AtlasReport, GlyphPosition, Packing};
+
+mod font;
+pub use font::{CharData, Font};
+
+mod folder;
+pub use folder::{read_folder, write_folder, MANIFEST_FILE_NAME};
+
+mod img_format;
+pub use img_format::ImgFormat;
+
+mod text_font;
+pub use text_font::{read_text_font, write_text_font};
+
+mod sheet;
+pub use sheet::{export_sheet, import_sheet, read_sheet, write_sheet, SheetExport};
+
+mod open;
+pub use open::{open_font, save_font};
+
+mod text;
+pub use text::{lookup_char, measure_line, wrap_text};
+
+mod render;
+pub use render::{
+    default_line_height, estimate_baseline, render_text, RenderOptions, RenderedText,
+};
+
+mod bmfont;
+pub use bmfont::{export_bmfont, import_bmfont, BmFontExport};
+
+mod bitmap_font;
+pub use bitmap_font::{export_bdf, import_bitmap_font, BdfAlpha};
+
+mod merge;
+pub use merge::{merge_fonts, MergeConflict, MergeSource, MergedFont};
+
+mod patch;
+pub use patch::{
+    apply_patch, make_patch, read_patch, write_patch, FontPatch, PatchChar, PATCH_FILE_NAME,
+};
+
+mod compare;
+pub use compare::{compare_fonts, display_char, render_differences, GlyphDifference};
+
+mod verify;
+pub use verify::round_trip;
+
+mod charmap;
+pub use charmap::{map_chars, parse_codepoint, read_char_mapping};
+
+mod unk;
+pub use unk::{UnkValues, DEFAULT_UNK4, DEFAULT_UNK5};
+
+mod charset;
+pub use charset::{parse_char_set, read_dic_char_set, CharSetPreset};
+
+mod message;
+pub use message::{extract_message_chars, message_text_chars};
+
+mod truetype;
+pub use truetype::{
+    import_truetype, read_char_list, ClippedGlyph, MissingGlyph, TrueTypeImport, TrueTypeOptions,
+    TrueTypeSource,
+};
+
+mod truetype_export;
+pub use truetype_export::export_truetype;
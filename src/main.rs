@@ -1,19 +1,20 @@
-use anyhow::{Context, Result, anyhow};
+use anyhow::{bail, Context, Result};
 use clap::Clap;
-use fontdue::{Font, FontSettings};
-use image::{DynamicImage, GenericImage, ImageBuffer, ImageFormat, LumaA, Rgba};
-use pmd_cte::{CteFormat, CteImage};
-use pmd_dic::{KandChar, KandFile};
-use std::collections::BTreeMap;
-use std::collections::BTreeSet;
-use std::convert::TryInto;
-use std::path::Path;
-use std::{fs::DirBuilder, io::Read, str::FromStr};
-use std::{
-    fs::{create_dir_all, read_dir, File},
-    path::PathBuf,
-    u16,
+use image::imageops::{resize, FilterType};
+use image::Rgba;
+use pmd_code_table::CodeTable;
+use pmdfonttool::{
+    apply_patch, compare_fonts, display_char, export_bdf, export_bmfont, export_truetype,
+    extract_message_chars, import_bitmap_font, import_bmfont, import_truetype, lookup_char,
+    make_patch, map_chars, measure_line, merge_fonts, open_font, parse_char_set, read_char_mapping,
+    read_patch, render_differences, render_text, round_trip, save_font, write_folder, write_patch,
+    write_sheet, AtlasOptions, BdfAlpha, Font, FontToolError, ImgFormat, MergeSource, MissingGlyph,
+    Packing, RenderOptions, TrueTypeOptions, TrueTypeSource, UnkValues,
 };
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{create_dir_all, read, read_dir, read_to_string, write, File};
+use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
 
 #[derive(Clap)]
 struct Opts {
@@ -25,10 +26,35 @@ struct Opts {
 enum SubCommand {
     /// Read a .dic file and a .img file, and generate a folder containing all the glyth
     Generate(GenerateParameter),
-    /// Read a folder in the format extracted by generate, and write it to a .dic and a .img
+    /// Build a .dic and a .img file from a folder, a text font or a grid sheet written by generate
     Build(BuildParameter),
     /// Read a truetype font, and export a folder that can be read by the build command
     FromTruetype(FromTruetypeParameter),
+    /// Check that generating then building a .dic and .img file give back the same glyphs
+    Verify(VerifyParameter),
+    /// Render a text with a font to a png image
+    Preview(PreviewParameter),
+    /// Measure the width in pixel of each line of a text
+    Measure(MeasureParameter),
+    /// Read an AngelCode BMFont font, and export it as a folder or a .dic and .img file
+    FromBmfont(FromBmfontParameter),
+    /// Read a BDF or PCF bitmap font, and export it as a folder or a .dic and .img file
+    FromBdf(FromBdfParameter),
+    /// Export a font to a BDF bitmap font, that can be edited in font editors
+    ToBdf(ToBdfParameter),
+    /// Convert a font to a TrueType font, with a square for each pixel
+    ToTruetype(ToTruetypeParameter),
+    /// List the characters used in game message files, to be used as the char set of a font
+    ExtractChars(ExtractCharsParameter),
+    /// Combine the glyphs of several fonts into one
+    Merge(MergeParameter),
+    /// List the glyphs that differ between two fonts
+    Diff(DiffParameter),
+    /// Record the glyphs that differ from a base font in a patch folder, that can be distributed
+    /// without the base font
+    MakePatch(MakePatchParameter),
+    /// Rebuild a modified font from its base font and a patch folder
+    ApplyPatch(ApplyPatchParameter),
 }
 
 #[derive(Clap)]
@@ -37,24 +63,55 @@ pub struct GenerateParameter {
     dic_input: PathBuf,
     /// the input .img file
     img_input: PathBuf,
-    /// the output folder
+    /// the output folder, or a .txt file to write the glyphs as text that can be reviewed in diffs
     output: PathBuf,
+    /// also export the font as an AngelCode BMFont .fnt file, with its page next to it
+    #[clap(long)]
+    bmfont: Option<PathBuf>,
+    /// write the BMFont .fnt file in the binary format instead of the text one
+    #[clap(long, requires = "bmfont")]
+    bmfont_binary: bool,
+    /// also draw every glyph in a png grid sheet, with a .json sidecar next to it storing the
+    /// character of each cell and its metrics. The edited sheet can be read back by build.
+    #[clap(long)]
+    sheet: Option<PathBuf>,
+    /// the number of cells in each row of the grid sheet
+    #[clap(long, default_value = "32")]
+    sheet_columns: u32,
 }
 
 #[derive(Clap)]
 pub struct BuildParameter {
-    /// the input folder
+    /// the input folder, a .txt text font or a .png grid sheet written by generate
     input: PathBuf,
     /// the output .dic file
     dic_output: PathBuf,
     /// the output .img file
     img_output: PathBuf,
+    /// the algorithm used to place the glyphs in the atlas (shelf, skyline or maxrects). By
+    /// default, the layout of the .img the folder was generated from is kept if the glyphs still
+    /// fit in it, and shelf is used otherwise.
+    #[clap(long)]
+    packing: Option<Packing>,
+    /// the minimal width of the atlas [default: 512]
+    #[clap(long)]
+    atlas_width: Option<u16>,
+    /// make the width and the height of the atlas a power of two
+    #[clap(long)]
+    power_of_two: bool,
+    /// the pixel format of the .img file (default to the format of the .img the folder was
+    /// generated from). Only a8 is supported by the version of pmd_cte in use.
+    #[clap(long)]
+    format: Option<ImgFormat>,
 }
 
 #[derive(Clap)]
 pub struct FromTruetypeParameter {
-    /// a file listing every character to be exported (UTF-8, can be present multiple time)
-    char_list_path: PathBuf,
+    /// the characters to be exported: a UTF-8 file listing them (can be present multiple time),
+    /// or a comma separated list of files, .dic files (to take the characters of an existing
+    /// font), presets (ascii, latin-1, latin-extended-a, cyrillic, greek, jis-x-0208-level-1,
+    /// gb2312) and codepoint ranges (like "U+0020-U+007E")
+    char_set: String,
     /// the input TrueType font
     input: PathBuf,
     /// the output folder
@@ -62,6 +119,266 @@ pub struct FromTruetypeParameter {
     /// the height of the generated font
     #[clap(default_value = "18")]
     scale: u16,
+    /// a file associating codepoints of the game with characters of the TrueType font, one
+    /// "<game codepoint> <source character>" pair per line (like "U+E000 U+1F600")
+    #[clap(long)]
+    mapping: Option<PathBuf>,
+    /// skip the characters outside of the Basic Multilingual Plane that aren't mapped, instead of
+    /// failing
+    #[clap(long)]
+    skip_non_bmp: bool,
+    /// the distance between the top of the line and the baseline, in pixel (default to the ascent
+    /// of the font)
+    #[clap(long)]
+    baseline: Option<i32>,
+    /// the height of a line, in pixel, used to report glyphs going out of the line (default to the
+    /// ascent minus the descent of the font)
+    #[clap(long)]
+    line_height: Option<u32>,
+    /// a TrueType font the characters missing from the previous fonts are taken from. Can be
+    /// repeated, the fonts being tried in order. Adjustments can be added after the path, like
+    /// "noto.otf,scale=16,x=1,y=-2" (the offsets moving the glyphs right and down, in pixel)
+    #[clap(long, number_of_values = 1, parse(try_from_str = parse_fallback_font))]
+    fallback: Vec<FallbackFont>,
+    /// what to do with the characters that aren't in the fonts: fail, skip or notdef (write the
+    /// .notdef glyph of the font)
+    #[clap(long, default_value = "skip")]
+    missing: MissingGlyph,
+    /// the unk4 value of the glyphs
+    #[clap(long, default_value = "10")]
+    unk4: u16,
+    /// the unk5 value of the glyphs
+    #[clap(long, default_value = "10")]
+    unk5: u16,
+    /// a .dic file to copy the unk4 and unk5 values of the characters it contains from
+    #[clap(long)]
+    unk_reference: Option<PathBuf>,
+    /// a file overriding the unk4 and unk5 values of some characters, one
+    /// "<codepoint> <unk4> <unk5>" entry per line (like "U+0041 10 12"). Has priority over
+    /// --unk-reference.
+    #[clap(long)]
+    unk_overrides: Option<PathBuf>,
+}
+
+#[derive(Clap)]
+pub struct VerifyParameter {
+    /// the input .dic file
+    dic_input: PathBuf,
+    /// the input .img file
+    img_input: PathBuf,
+}
+
+#[derive(Clap)]
+pub struct PreviewParameter {
+    /// the font: a folder, a .txt text font, a .png grid sheet, or a .dic file with the .img file
+    /// next to it
+    input: PathBuf,
+    /// the text to render
+    text: String,
+    /// the output png file
+    output: PathBuf,
+    /// wrap the text so it fit in this width (in pixel)
+    #[clap(long)]
+    box_width: Option<u32>,
+    /// the distance between two lines (in pixel). Default to the height of the font.
+    #[clap(long)]
+    line_height: Option<u32>,
+    /// the color of the text, as a RRGGBB or RRGGBBAA hexadecimal value
+    #[clap(long, default_value = "ffffff", parse(try_from_str = parse_color))]
+    color: Rgba<u8>,
+    /// the color of the background, as a RRGGBB or RRGGBBAA hexadecimal value
+    #[clap(long, default_value = "000000", parse(try_from_str = parse_color))]
+    background: Rgba<u8>,
+}
+
+#[derive(Clap)]
+pub struct MeasureParameter {
+    /// the font: a folder, a .txt text font, a .png grid sheet, or a .dic file with the .img file
+    /// next to it
+    input: PathBuf,
+    /// the text to measure
+    #[clap(long, conflicts_with_all = &["file", "list"], required_unless_present_any = &["file", "list"])]
+    text: Option<String>,
+    /// an UTF-8 file, whose every line is measured
+    #[clap(long, conflicts_with = "list")]
+    file: Option<PathBuf>,
+    /// an UTF-8 file with one entry per line, where "\n" is a line break inside of an entry
+    #[clap(long)]
+    list: Option<PathBuf>,
+    /// report the lines wider than this width (in pixel), and fail if there are any
+    #[clap(long)]
+    max_width: Option<u32>,
+}
+
+#[derive(Clap)]
+pub struct FromBmfontParameter {
+    /// the input .fnt file (text or binary), with its pages next to it
+    input: PathBuf,
+    /// the output: a .dic file (the .img file is written next to it), a .txt text font, or a folder
+    output: PathBuf,
+}
+
+#[derive(Clap)]
+pub struct FromBdfParameter {
+    /// the input .bdf or .pcf file (eventually compressed with gzip)
+    input: PathBuf,
+    /// the output: a .dic file (the .img file is written next to it), a .txt text font, or a folder
+    output: PathBuf,
+    /// the characters to export, in the same format as the char set of from-truetype. Every
+    /// character of the font is exported by default.
+    #[clap(long)]
+    char_list: Option<String>,
+}
+
+#[derive(Clap)]
+pub struct ToBdfParameter {
+    /// the font: a folder, a .txt text font, a .png grid sheet, or a .dic file with the .img file
+    /// next to it
+    input: PathBuf,
+    /// the output .bdf file
+    output: PathBuf,
+    /// the minimal alpha (between 0 and 255) of a pixel to be set in the BDF font
+    #[clap(long, default_value = "128", conflicts_with = "greymap")]
+    threshold: u8,
+    /// keep the alpha by writing a BDF 2.3 greymap font with 4 bit per pixel
+    #[clap(long)]
+    greymap: bool,
+}
+
+#[derive(Clap)]
+pub struct ToTruetypeParameter {
+    /// the font: a folder, a .txt text font, a .png grid sheet, or a .dic file with the .img file
+    /// next to it
+    input: PathBuf,
+    /// the output .ttf file
+    output: PathBuf,
+    /// the minimal alpha (between 1 and 255) of a pixel to be drawn in the TrueType font
+    #[clap(long, default_value = "128")]
+    threshold: u8,
+    /// the family name of the font. Default to the name of the output file.
+    #[clap(long)]
+    name: Option<String>,
+}
+
+#[derive(Clap)]
+pub struct ExtractCharsParameter {
+    /// the message files: FARC archives (like message_us.bin), single message files, UTF-8
+    /// texts, or folders containing .bin and .txt files
+    #[clap(required = true)]
+    input: Vec<PathBuf>,
+    /// the output file, listing the used characters as UTF-8
+    #[clap(short, long)]
+    output: PathBuf,
+    /// the code_table.bin file of the game, used to recognize the control codes of binary
+    /// message files
+    #[clap(long)]
+    code_table: Option<PathBuf>,
+}
+
+#[derive(Clap)]
+pub struct MergeParameter {
+    /// the fonts to merge, by decreasing priority: folders, .txt text fonts, .png grid sheets or
+    /// .dic files with the .img file next to them. Only some characters of a font can be taken by
+    /// following its path with "=" and a char set in the format used by from-truetype, like
+    /// "latin/=U+0020-U+007E,U+00E9" (the path is split at the last "=").
+    #[clap(required = true)]
+    input: Vec<String>,
+    /// the output: a .dic file (the .img file is written next to it), a .txt text font, or a folder
+    #[clap(short, long)]
+    output: PathBuf,
+    /// fail when a character is provided with different glyphs by several fonts, instead of
+    /// taking the glyph of the first one
+    #[clap(long)]
+    fail_on_conflict: bool,
+}
+
+#[derive(Clap)]
+pub struct DiffParameter {
+    /// the old font: a folder, a .txt text font, a .png grid sheet, or a .dic file with the
+    /// .img file next to it
+    old: PathBuf,
+    /// the new font: a folder, a .txt text font, a .png grid sheet, or a .dic file with the
+    /// .img file next to it
+    new: PathBuf,
+    /// write a png image showing the old and new glyph of each changed character side by side
+    #[clap(long)]
+    image: Option<PathBuf>,
+    /// the zoom factor of the diff image
+    #[clap(long, default_value = "4")]
+    zoom: u32,
+}
+
+#[derive(Clap)]
+pub struct MakePatchParameter {
+    /// the base .dic file, with the .img file next to it
+    base: PathBuf,
+    /// the modified font: a folder, a .txt text font, a .png grid sheet, or a .dic file with the
+    /// .img file next to it
+    modified: PathBuf,
+    /// the output patch folder
+    output: PathBuf,
+}
+
+#[derive(Clap)]
+pub struct ApplyPatchParameter {
+    /// the base .dic file, with the .img file next to it
+    base: PathBuf,
+    /// the patch folder
+    patch: PathBuf,
+    /// the output: a .dic file (the .img file is written next to it), a .txt text font, or a folder
+    output: PathBuf,
+}
+
+fn read_base_font(dic_path: &Path) -> Result<(Vec<u8>, Vec<u8>)> {
+    let img_path = dic_path.with_extension("img");
+    let dic = read(dic_path).with_context(|| format!("can't read the file at {:?}", dic_path))?;
+    let img = read(&img_path).with_context(|| format!("can't read the file at {:?}", img_path))?;
+    Ok((dic, img))
+}
+
+fn parse_color(text: &str) -> Result<Rgba<u8>> {
+    let text = text.trim_start_matches('#');
+    let value = u32::from_str_radix(text, 16)
+        .with_context(|| format!("{:?} isn't an hexadecimal color", text))?;
+    Ok(match text.len() {
+        6 => Rgba([(value >> 16) as u8, (value >> 8) as u8, value as u8, 255]),
+        8 => Rgba(value.to_be_bytes()),
+        _ => bail!("the color {:?} should have 6 or 8 hexadecimal digits", text),
+    })
+}
+
+pub struct FallbackFont {
+    path: PathBuf,
+    scale: Option<u16>,
+    x_offset: i16,
+    y_offset: i16,
+}
+
+fn parse_fallback_font(text: &str) -> Result<FallbackFont> {
+    let mut fallback = FallbackFont {
+        path: PathBuf::new(),
+        scale: None,
+        x_offset: 0,
+        y_offset: 0,
+    };
+    // the adjustments are read from the end, so the path itself can contain commas
+    let mut path = text;
+    while let Some((rest, adjustment)) = path.rsplit_once(',') {
+        let (key, value) = match adjustment.split_once('=') {
+            Some(pair) => pair,
+            None => break,
+        };
+        let invalid_value = || format!("invalid value {:?} for {:?}", value, key);
+        match key.trim() {
+            "scale" => fallback.scale = Some(value.trim().parse().with_context(invalid_value)?),
+            "x" => fallback.x_offset = value.trim().parse().with_context(invalid_value)?,
+            "y" => fallback.y_offset = value.trim().parse().with_context(invalid_value)?,
+            _ => bail!("unknown font adjustment {:?} (expected scale, x or y)", key),
+        };
+        path = rest;
+    }
+    fallback.path = PathBuf::from(path);
+    Ok(fallback)
 }
 
 fn main() -> Result<()> {
@@ -72,234 +389,597 @@ fn main() -> Result<()> {
         SubCommand::FromTruetype(fp) => {
             from_truetype(fp).context("can't generate the font result from the TrueType font")?
         }
+        SubCommand::Verify(vp) => verify(vp).context("can't verify the round trip of the font")?,
+        SubCommand::Preview(pp) => preview(pp).context("can't render the preview")?,
+        SubCommand::Measure(mp) => measure(mp).context("can't measure the text")?,
+        SubCommand::FromBdf(fp) => {
+            from_bdf(fp).context("can't convert the font from the bitmap font")?
+        }
+        SubCommand::ToBdf(tp) => to_bdf(tp).context("can't export the font to BDF")?,
+        SubCommand::ToTruetype(tp) => {
+            to_truetype(tp).context("can't convert the font to TrueType")?
+        }
+        SubCommand::FromBmfont(fp) => {
+            from_bmfont(fp).context("can't convert the font from the BMFont font")?
+        }
+        SubCommand::ExtractChars(ep) => {
+            extract_chars(ep).context("can't extract the characters of the messages")?
+        }
+        SubCommand::Merge(mp) => merge(mp).context("can't merge the fonts")?,
+        SubCommand::Diff(dp) => diff(dp).context("can't compare the fonts")?,
+        SubCommand::MakePatch(mp) => make_patch_folder(mp).context("can't make the patch")?,
+        SubCommand::ApplyPatch(ap) => apply_patch_folder(ap).context("can't apply the patch")?,
     };
     Ok(())
 }
 
-pub struct CharData {
-    pub glyth_width: u16,
-    pub glyth_height: u16,
-    pub xalign: i16,
-    pub yalign: i16,
-    pub distance: u16,
-    pub unk4: u16,
-    pub unk5: u16,
-    pub image: ImageBuffer<Rgba<u8>, Vec<u8>>,
-}
-
 fn generate(gp: GenerateParameter) -> Result<()> {
     println!("generating the editable font into {:?}", &gp.output);
-    let mut input_kand = File::open(&gp.dic_input)?;
-    let kand = KandFile::new_from_reader(&mut input_kand)?;
-    let mut input_cte = File::open(&gp.img_input)?;
-    let mut cte = CteImage::decode_cte(&mut input_cte)?;
-    create_dir_all(&gp.output)?;
-    for char in kand.chars {
-        //TODO: this could panic
-        let section = cte.image.crop(
-            char.start_x as u32,
-            char.start_y as u32,
-            char.glyth_width as u32,
-            char.glyth_height as u32,
-        );
-        let file_name = format!(
-            "{}_{}_{}_{}_{}_{}.png",
-            char.char, char.unk1, char.unk2, char.distance, char.unk4, char.unk5
-        );
-        let target_file = gp.output.join(file_name);
-        section.save(target_file)?;
-    }
+    let mut input_kand = File::open(&gp.dic_input)
+        .with_context(|| format!("can't open the file at {:?}", gp.dic_input))?;
+    let mut input_cte = File::open(&gp.img_input)
+        .with_context(|| format!("can't open the file at {:?}", gp.img_input))?;
+    let font = Font::load(&mut input_kand, &mut input_cte)?;
+    save_font(&font, &gp.output)?;
+    if let Some(fnt_path) = &gp.bmfont {
+        let face = fnt_path
+            .file_stem()
+            .context("the BMFont output path doesn't have a file name")?
+            .to_string_lossy()
+            .to_string();
+        let page_file = format!("{}_0.png", face);
+        let page_path = fnt_path.with_file_name(&page_file);
+        println!("exporting the BMFont to {:?} and {:?}", fnt_path, page_path);
+        let export = export_bmfont(&font, &face, &page_file, gp.bmfont_binary)?;
+        if let Some(parent) = fnt_path.parent() {
+            create_dir_all(parent)
+                .with_context(|| format!("can't create the directory {:?}", parent))?;
+        };
+        write(fnt_path, &export.fnt)
+            .with_context(|| format!("can't write the file at {:?}", fnt_path))?;
+        export
+            .page
+            .save(&page_path)
+            .with_context(|| format!("can't save the image at {:?}", page_path))?;
+    };
+    if let Some(sheet_path) = &gp.sheet {
+        println!("drawing the grid sheet to {:?}", sheet_path);
+        if let Some(parent) = sheet_path.parent() {
+            create_dir_all(parent)
+                .with_context(|| format!("can't create the directory {:?}", parent))?;
+        };
+        write_sheet(&font, sheet_path, gp.sheet_columns)?;
+    };
     println!("done");
     Ok(())
 }
 
 fn build(bp: BuildParameter) -> Result<()> {
-    println!("starting the generation of {:?} and {:?}", bp.dic_output, bp.img_output);
-    // 1: read the input
-    let mut chars_data = BTreeMap::new();
-    for char_file_maybe in read_dir(&bp.input)? {
-        let char_file = char_file_maybe?;
-        let char_path = char_file.path();
-        let stem = char_path
-            .file_stem()
-            .with_context(|| format!("the file at {:?} doesn't have a valid name", char_path))?
-            .to_string_lossy();
-        let mut splited = stem.split('_');
+    println!(
+        "starting the generation of {:?} and {:?}",
+        bp.dic_output, bp.img_output
+    );
+    let mut font = open_font(&bp.input)?;
+    if let Some(format) = bp.format {
+        font.img_format = format;
+    };
+    let mut kand_writer = File::create(&bp.dic_output)
+        .with_context(|| format!("can't create the file at {:?}", bp.dic_output))?;
+    let mut cte_writer = File::create(&bp.img_output)
+        .with_context(|| format!("can't create the file at {:?}", bp.img_output))?;
+    let atlas_options = AtlasOptions {
+        packing: bp.packing.unwrap_or(Packing::Shelf),
+        width: bp.atlas_width.unwrap_or(512),
+        power_of_two: bp.power_of_two,
+        keep_original_layout: bp.packing.is_none() && bp.atlas_width.is_none() && !bp.power_of_two,
+    };
+    let report = font.save(&mut kand_writer, &mut cte_writer, &atlas_options)?;
+    if report.original_layout {
+        println!("reused the atlas layout of the original .img file");
+    };
+    println!(
+        "atlas of {}x{} pixels, {:.1}% filled",
+        report.width,
+        report.height,
+        report.fill_ratio() * 100.0
+    );
+    println!("done");
+    Ok(())
+}
 
-        fn get_text_from_file_name<'a>(iter: &mut impl Iterator<Item = &'a str>, file_path: &Path) -> Result<&'a str> {
-            iter.next().with_context(|| format!("the path {:?} doesn't have the good format of \"charid_unk1_unk2_distance_unk4_unk5.ext\"", file_path))
+fn from_truetype(fp: FromTruetypeParameter) -> Result<()> {
+    let chars_to_include = parse_char_set(&fp.char_set)
+        .with_context(|| format!("can't read the char set {:?}", fp.char_set))?;
+    let mapping = match &fp.mapping {
+        Some(mapping_path) => {
+            let mut mapping_file = File::open(mapping_path)
+                .with_context(|| format!("can't open the file at {:?}", mapping_path))?;
+            read_char_mapping(&mut mapping_file)
+                .with_context(|| format!("can't read the char mapping at {:?}", mapping_path))?
         }
+        None => BTreeMap::new(),
+    };
+    let (chars_to_include, skipped_chars) =
+        map_chars(&chars_to_include, &mapping, fp.skip_non_bmp)?;
+    if !skipped_chars.is_empty() {
+        println!(
+            "skipped the characters outside of the Basic Multilingual Plane: {}",
+            skipped_chars.iter().collect::<String>()
+        );
+    };
 
-        fn read_u16_from_splited<'a>(iter: &mut impl Iterator<Item = &'a str>, file_path: &Path) -> Result<u16>{
-            let text = get_text_from_file_name(iter, file_path)?;
-            Ok(u16::from_str(text).with_context(|| {
-                format!(
-                    "can't transform the text {:?} to a u16 number (for the file name at {:?})",
-                    text, file_path
-                )
-            })?)
-        }
+    let read_font = |path: &PathBuf| {
+        read(path)
+            .with_context(|| format!("can't read the complete content of the file at {:?}", path))
+    };
+    let input_bytes = read_font(&fp.input)?;
+    let fallback_bytes = fp
+        .fallback
+        .iter()
+        .map(|fallback| read_font(&fallback.path))
+        .collect::<Result<Vec<_>>>()?;
+    let mut sources = vec![TrueTypeSource::new(&input_bytes)];
+    for (fallback, bytes) in fp.fallback.iter().zip(&fallback_bytes) {
+        sources.push(TrueTypeSource {
+            bytes,
+            scale: fallback.scale,
+            x_offset: fallback.x_offset,
+            y_offset: fallback.y_offset,
+        });
+    }
 
-        fn read_i16_from_splited<'a>(iter: &mut impl Iterator<Item = &'a str>, file_path: &Path) -> Result<i16>{
-            let text = get_text_from_file_name(iter, file_path)?;
-            Ok(i16::from_str(text).with_context(|| {
-                format!(
-                    "can't transform the text {:?} to a i16 number (for the file name at {:?})",
-                    text, file_path
-                )
-            })?)
-        }
+    let mut unk = UnkValues {
+        unk4: fp.unk4,
+        unk5: fp.unk5,
+        ..UnkValues::default()
+    };
+    if let Some(reference_path) = &fp.unk_reference {
+        let reference_file = File::open(reference_path)
+            .with_context(|| format!("can't open the file at {:?}", reference_path))?;
+        unk.copy_from_dic(&mut BufReader::new(reference_file))
+            .with_context(|| format!("can't read the .dic file at {:?}", reference_path))?;
+    };
+    if let Some(overrides_path) = &fp.unk_overrides {
+        let mut overrides_file = File::open(overrides_path)
+            .with_context(|| format!("can't open the file at {:?}", overrides_path))?;
+        unk.read_overrides(&mut overrides_file)
+            .with_context(|| format!("can't read the unk overrides at {:?}", overrides_path))?;
+    };
 
-        let char_id = read_u16_from_splited(&mut splited, &char_path)?;
-        let unk1 = read_i16_from_splited(&mut splited, &char_path)?;
-        let unk2 = read_i16_from_splited(&mut splited, &char_path)?;
-        let distance = read_u16_from_splited(&mut splited, &char_path)?;
-        let unk4 = read_u16_from_splited(&mut splited, &char_path)?;
-        let unk5 = read_u16_from_splited(&mut splited, &char_path)?;
-
-        let char_image = image::io::Reader::open(&char_path)?.decode()?.to_rgba8();
-        let glyth_width = char_image.width().try_into().context("The width of the glyth is to big")?;
-        let glyth_height = char_image.height().try_into().context("The height of the glyth is too big")?;
-        if let Some(_) = chars_data.insert(char_id, CharData {
-            glyth_height,
-            glyth_width,
-            xalign: unk1,
-            yalign: unk2,
-            distance,
-            unk4,
-            unk5,
-            image: char_image,
-        }) {
-            return Err(anyhow!("The file {:?} represent a character that is also used by another file", char_path));
-        }
-    }
-    
-    // 2. create the atlas
-    let mut atlas_width = 512;
-    let mut chars = Vec::new();
-    let mut max_width = 0;
-    let mut lower_y = 0;
-    let mut pos_x = 0;
-    let mut pos_y = 0;
-
-    for (char_id, char_data) in chars_data {
-        // also, place the char
-        let x_at_end_of_char = pos_x + char_data.glyth_width;
-        if x_at_end_of_char >= atlas_width {
-            pos_x = 0;
-            pos_y = lower_y;
+    println!("rasterizing {} characters", chars_to_include.len());
+    let options = TrueTypeOptions {
+        scale: fp.scale,
+        baseline: fp.baseline,
+        line_height: fp.line_height,
+        missing: fp.missing,
+        unk,
+    };
+    let imported = import_truetype(&sources, &chars_to_include, &options)?;
+    println!(
+        "baseline at {} pixel, line height of {} pixel",
+        imported.baseline, imported.line_height
+    );
+    for clipped in &imported.clipped {
+        if clipped.above != 0 {
+            println!(
+                "glyph {:?} (0x{:04X}) goes {} pixel above the line",
+                clipped.chara, clipped.char_id, clipped.above
+            );
+        };
+        if clipped.below != 0 {
+            println!(
+                "glyph {:?} (0x{:04X}) goes {} pixel below the line",
+                clipped.chara, clipped.char_id, clipped.below
+            );
         };
-        let start_x = pos_x;
-        let start_y = pos_y;
-        lower_y = lower_y.max(pos_y + char_data.glyth_height);
-        pos_x += char_data.glyth_width;
-        max_width = max_width.max(char_data.glyth_width);
-        let char = KandChar {
-            char: char_id,
-            start_x,
-            start_y,
-            glyth_width: char_data.glyth_width,
-            glyth_height: char_data.glyth_height,
-            unk1: char_data.xalign,
-            unk2: char_data.yalign,
-            distance: char_data.distance,
-            unk4: char_data.unk4,
-            unk5: char_data.unk5,
+    }
+    for (font_index, fallback) in fp.fallback.iter().enumerate() {
+        let taken_chars: String = imported
+            .from_fallback
+            .iter()
+            .filter(|(_, _, index)| *index == font_index + 1)
+            .map(|(_, chara, _)| *chara)
+            .collect();
+        if !taken_chars.is_empty() {
+            println!(
+                "{} characters were taken from {:?}: {:?}",
+                taken_chars.chars().count(),
+                fallback.path,
+                taken_chars
+            );
         };
-        chars.push((char, char_data.image));
     }
+    if !imported.missing.is_empty() {
+        println!(
+            "{} characters aren't in the font{}:",
+            imported.missing.len(),
+            if fp.missing == MissingGlyph::Notdef {
+                " (replaced by the .notdef glyph)"
+            } else {
+                " (skipped)"
+            }
+        );
+        for (char_id, chara) in &imported.missing {
+            println!("  {:?} (0x{:04X})", chara, char_id);
+        }
+    };
+    write_folder(&imported.font, &fp.output)?;
+    Ok(())
+}
 
-    atlas_width = ((atlas_width.max(max_width) - 1) / 8 + 1) * 8;
-    lower_y = ((lower_y - 1) / 8 + 1) * 8;
-    let mut atlas: ImageBuffer<Rgba<u8>, Vec<u8>> =
-        ImageBuffer::new(atlas_width as u32, lower_y as u32);
-    for (char_data, char_image) in &chars {
-        atlas.copy_from(
-            char_image,
-            char_data.start_x as u32,
-            char_data.start_y as u32,
-        )?;
+fn verify(vp: VerifyParameter) -> Result<()> {
+    let dic_bytes = read(&vp.dic_input)
+        .with_context(|| format!("can't read the file at {:?}", vp.dic_input))?;
+    let img_bytes = read(&vp.img_input)
+        .with_context(|| format!("can't read the file at {:?}", vp.img_input))?;
+    let original = Font::load(&mut Cursor::new(&dic_bytes), &mut Cursor::new(&img_bytes))?;
+    let (rebuilt_dic, rebuilt_img) = round_trip(&original)?;
+    let rebuilt = Font::load(
+        &mut Cursor::new(&rebuilt_dic),
+        &mut Cursor::new(&rebuilt_img),
+    )
+    .context("can't read the rebuilt font")?;
+
+    let differences = compare_fonts(&original, &rebuilt);
+    for difference in &differences {
+        println!("{}", difference);
     }
-    atlas.save("./test.png")?;
+    println!(
+        "{} characters checked, {} differences",
+        original.chars.len(),
+        differences.len()
+    );
+    for (name, original_bytes, rebuilt_bytes) in &[
+        (".dic", &dic_bytes, &rebuilt_dic),
+        (".img", &img_bytes, &rebuilt_img),
+    ] {
+        if original_bytes == rebuilt_bytes {
+            println!("the rebuilt {} file is identical to the original", name);
+        } else {
+            println!(
+                "the rebuilt {} file isn't byte-for-byte identical to the original",
+                name
+            );
+        }
+    }
+    if !differences.is_empty() {
+        bail!("the rebuilt font is different from the original");
+    };
+    Ok(())
+}
 
-    // 3. save it
-    let kand_file = KandFile {
-        unk1: 0,
-        unk2: 0,
-        chars: chars.into_iter().map(|e| e.0).collect(),
+fn preview(pp: PreviewParameter) -> Result<()> {
+    let font = open_font(&pp.input)?;
+    let options = RenderOptions {
+        box_width: pp.box_width,
+        line_height: pp.line_height,
+        color: pp.color,
+        background: pp.background,
+    };
+    let rendered = render_text(&font, &pp.text, &options);
+    if !rendered.missing_chars.is_empty() {
+        println!(
+            "warning: those characters are not in the font: {:?}",
+            rendered.missing_chars
+        );
     };
-    let mut kand_writer = File::create(bp.dic_output)?;
-    kand_file.write(&mut kand_writer)?;
+    rendered
+        .image
+        .save(&pp.output)
+        .with_context(|| format!("can't save the image at {:?}", pp.output))?;
+    Ok(())
+}
 
-    let cte_image = CteImage {
-        original_format: CteFormat::A8,
-        image: DynamicImage::ImageRgba8(atlas),
+fn measure(mp: MeasureParameter) -> Result<()> {
+    let font = open_font(&mp.input)?;
+    let read_text = |path: &PathBuf| {
+        read_to_string(path).with_context(|| format!("can't read the text file at {:?}", path))
+    };
+    // each entry is a list of line
+    let entries: Vec<Vec<String>> = if let Some(text) = &mp.text {
+        vec![text.split('\n').map(|line| line.to_string()).collect()]
+    } else if let Some(file) = &mp.file {
+        read_text(file)?
+            .lines()
+            .map(|line| vec![line.to_string()])
+            .collect()
+    } else if let Some(list) = &mp.list {
+        read_text(list)?
+            .lines()
+            .map(|entry| entry.split("\\n").map(|line| line.to_string()).collect())
+            .collect()
+    } else {
+        unreachable!()
     };
 
-    let mut cte_writer = File::create(bp.img_output)?;
-    cte_image.encode_cte(&mut cte_writer)?;
-    println!("done");
+    let mut overflowing_lines = 0;
+    let mut all_missing_chars = BTreeSet::new();
+    for (entry_index, entry) in entries.iter().enumerate() {
+        for (line_index, line) in entry.iter().enumerate() {
+            let (width, missing_chars) = measure_line(&font, line);
+            let mut report = if entry.len() > 1 {
+                format!("{}.{}: {}px", entry_index + 1, line_index + 1, width)
+            } else {
+                format!("{}: {}px", entry_index + 1, width)
+            };
+            if let Some(max_width) = mp.max_width {
+                if width > max_width {
+                    overflowing_lines += 1;
+                    report.push_str(&format!(" (too wide by {}px)", width - max_width));
+                }
+            };
+            if !missing_chars.is_empty() {
+                report.push_str(&format!(" missing characters: {:?}", missing_chars));
+                all_missing_chars.extend(missing_chars);
+            };
+            println!("{}\t{}", report, line);
+        }
+    }
+    if !all_missing_chars.is_empty() {
+        println!(
+            "those characters are not in the font: {}",
+            all_missing_chars.iter().collect::<String>()
+        );
+    };
+    if overflowing_lines != 0 {
+        bail!(
+            "{} lines are wider than the maximum width",
+            overflowing_lines
+        );
+    };
+    Ok(())
+}
+
+fn from_bmfont(fp: FromBmfontParameter) -> Result<()> {
+    let fnt = read(&fp.input).with_context(|| format!("can't read the file at {:?}", fp.input))?;
+    let font = import_bmfont(&fnt, |page_file| {
+        let page_path = fp.input.with_file_name(page_file);
+        Ok(image::open(&page_path)
+            .map_err(|err| FontToolError::ImageReadError(err, page_path))?
+            .to_rgba8())
+    })?;
+    println!("writing {} characters to {:?}", font.chars.len(), fp.output);
+    save_font(&font, &fp.output)?;
+    Ok(())
+}
+
+fn from_bdf(fp: FromBdfParameter) -> Result<()> {
+    let data = read(&fp.input).with_context(|| format!("can't read the file at {:?}", fp.input))?;
+    let mut font = import_bitmap_font(&data)?;
+    if let Some(char_list) = &fp.char_list {
+        let chars_to_include = parse_char_set(char_list)
+            .with_context(|| format!("can't read the char set {:?}", char_list))?;
+        let missing_chars: String = chars_to_include
+            .iter()
+            .filter(|chara| lookup_char(&font, **chara).is_none())
+            .collect();
+        if !missing_chars.is_empty() {
+            println!("those characters are not in the font: {}", missing_chars);
+        };
+        font.chars.retain(|char_id, _| {
+            std::char::from_u32(*char_id as u32).is_some_and(|c| chars_to_include.contains(&c))
+        });
+    };
+    println!("writing {} characters to {:?}", font.chars.len(), fp.output);
+    save_font(&font, &fp.output)?;
     Ok(())
 }
 
-pub fn from_truetype(fp: FromTruetypeParameter) -> Result<()> {
-    DirBuilder::new()
-        .recursive(true)
-        .create(&fp.output)
-        .with_context(|| format!("can't create the target directory {:?}", fp.output))?;
-
-    // list char to export
-    let mut chars_to_include: BTreeSet<char> = BTreeSet::new();
-    let mut char_list_file = File::open(&fp.char_list_path).context("can't open the file containing the list of char")?;
-    let mut char_list_buffer = Vec::new();
-    char_list_file.read_to_end(&mut char_list_buffer).context("can't read the file containing the char list")?;
-    let char_list_string = String::from_utf8(char_list_buffer).context("The file contining the char list isn't a valid UTF-8 file")?;
-    for chara in char_list_string.chars() {
-        chars_to_include.insert(chara);
+fn to_bdf(tp: ToBdfParameter) -> Result<()> {
+    let font = open_font(&tp.input)?;
+    let name = tp
+        .output
+        .file_stem()
+        .context("the output path doesn't have a file name")?
+        .to_string_lossy()
+        .to_string();
+    let alpha = if tp.greymap {
+        BdfAlpha::Greymap
+    } else {
+        BdfAlpha::Threshold(tp.threshold)
     };
+    let bdf = export_bdf(&font, &name, alpha)?;
+    write(&tp.output, bdf).with_context(|| format!("can't write the file at {:?}", tp.output))?;
+    Ok(())
+}
 
-    // read font
-    let mut ttf_file =
-        File::open(&fp.input).with_context(|| format!("can't open the file at {:?}", fp.input))?;
-    let mut ttf_bytes = Vec::new();
-    ttf_file.read_to_end(&mut ttf_bytes).with_context(|| {
-        format!(
-            "can't read the complete content of the file at {:?}",
-            fp.input
-        )
-    })?;
-    let ttf_font = Font::from_bytes(
-        ttf_bytes,
-        FontSettings {
-            scale: fp.scale as f32,
-            ..Default::default()
-        },
-    )
-    .unwrap(); //TODO: make it work with anyhow
-
-    for char in chars_to_include {
-        println!("rasterizing {:?}", char);
-        let (metric, bitmap_luminance) = ttf_font.rasterize(char, fp.scale as f32);
-        let char_image: ImageBuffer<LumaA<u8>, Vec<_>> = if metric.width != 0 && metric.height != 0 {
-            let mut bitmap: Vec<u8> = Vec::new();
-            for pixel in bitmap_luminance.into_iter() {
-                bitmap.push(0);
-                bitmap.push(pixel);
+fn to_truetype(tp: ToTruetypeParameter) -> Result<()> {
+    let font = open_font(&tp.input)?;
+    let name = match &tp.name {
+        Some(name) => name.clone(),
+        None => tp
+            .output
+            .file_stem()
+            .context("the output path doesn't have a file name")?
+            .to_string_lossy()
+            .to_string(),
+    };
+    let ttf = export_truetype(&font, &name, tp.threshold)?;
+    write(&tp.output, ttf).with_context(|| format!("can't write the file at {:?}", tp.output))?;
+    Ok(())
+}
+
+fn collect_message_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if path.is_dir() {
+        let mut entries = read_dir(path)
+            .with_context(|| format!("can't read the folder at {:?}", path))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()
+            .with_context(|| format!("can't read the folder at {:?}", path))?;
+        entries.sort();
+        for entry in entries {
+            let is_message = entry.extension().is_some_and(|extension| {
+                extension.eq_ignore_ascii_case("bin") || extension.eq_ignore_ascii_case("txt")
+            });
+            if entry.is_dir() || is_message {
+                collect_message_files(&entry, files)?;
             };
-            ImageBuffer::from_vec(metric.width as u32, metric.height as u32, bitmap)
-                    .with_context(|| format!("can't read the decoded character {:?}", char))?
+        }
+    } else {
+        files.push(path.to_path_buf());
+    };
+    Ok(())
+}
 
-        } else {
-            ImageBuffer::new(1, 1)
+fn extract_chars(ep: ExtractCharsParameter) -> Result<()> {
+    let code_table = match &ep.code_table {
+        Some(code_table_path) => {
+            let code_table_file = File::open(code_table_path)
+                .with_context(|| format!("can't open the file at {:?}", code_table_path))?;
+            let mut code_table = CodeTable::new_from_file(BufReader::new(code_table_file))
+                .with_context(|| format!("can't read the code table at {:?}", code_table_path))?;
+            code_table.add_missing();
+            Some(code_table)
+        }
+        None => None,
+    };
+    let code_to_text = code_table
+        .as_ref()
+        .map(|code_table| code_table.generate_code_to_text());
+
+    let mut files = Vec::new();
+    for input in &ep.input {
+        collect_message_files(input, &mut files)?;
+    }
+    let mut chars = BTreeSet::new();
+    let mut binary_without_code_table = false;
+    for file in &files {
+        let data = read(file).with_context(|| format!("can't read the file at {:?}", file))?;
+        if code_to_text.is_none() && (data.starts_with(b"FARC") || data.starts_with(b"SIR0")) {
+            binary_without_code_table = true;
+        };
+        chars.extend(
+            extract_message_chars(&data, code_to_text.as_ref())
+                .with_context(|| format!("can't read the messages of {:?}", file))?,
+        );
+    }
+    if binary_without_code_table {
+        println!("warning: binary message files were read without a code table, their control codes are listed as characters");
+    };
+    println!(
+        "found {} different characters in {} files",
+        chars.len(),
+        files.len()
+    );
+    write(&ep.output, chars.iter().collect::<String>())
+        .with_context(|| format!("can't write the file at {:?}", ep.output))?;
+    Ok(())
+}
+
+fn merge(mp: MergeParameter) -> Result<()> {
+    let mut fonts = Vec::new();
+    for input in &mp.input {
+        // split at the last "=", so the path itself can contain one, unless the whole input is
+        // an existing font
+        let (path, char_set) = match input.rsplit_once('=') {
+            Some((path, char_set)) if !Path::new(input).exists() => (path, Some(char_set)),
+            _ => (input.as_str(), None),
+        };
+        let font = open_font(Path::new(path))?;
+        let chars = match char_set {
+            Some(char_set) => Some(
+                parse_char_set(char_set)
+                    .with_context(|| format!("can't read the char set {:?}", char_set))?,
+            ),
+            None => None,
         };
-        //TODO: better parameter
-        let file_name = format!("{}_{}_{}_{}_10_10.png", char as u16, metric.xmin as i16, -metric.ymin as i16 + fp.scale as i16 - metric.height as i16, metric.advance_width as i16);
-        let mut out_char_path = fp.output.clone();
-        out_char_path.push(file_name);
-        char_image
-            .save_with_format(&out_char_path, ImageFormat::Png)
-            .with_context(|| format!("can't create/encode the image at {:?}", out_char_path))?;
+        fonts.push((path, font, chars));
     }
+    let sources: Vec<MergeSource> = fonts
+        .iter()
+        .map(|(_, font, chars)| MergeSource {
+            font,
+            chars: chars.as_ref(),
+        })
+        .collect();
+    let merged = merge_fonts(&sources);
+    for ((path, _, _), taken) in fonts.iter().zip(&merged.taken) {
+        println!("{} characters taken from {:?}", taken, path);
+    }
+    if !merged.conflicts.is_empty() {
+        println!(
+            "{} characters have different glyphs in several fonts:",
+            merged.conflicts.len()
+        );
+        for conflict in &merged.conflicts {
+            let paths: Vec<&str> = conflict
+                .sources
+                .iter()
+                .map(|index| fonts[*index].0)
+                .collect();
+            println!(
+                "  {} in {}",
+                display_char(conflict.char_id),
+                paths.join(", ")
+            );
+        }
+        if mp.fail_on_conflict {
+            bail!(
+                "{} characters are conflicting between the fonts",
+                merged.conflicts.len()
+            );
+        };
+    };
+    println!(
+        "writing {} characters to {:?}",
+        merged.font.chars.len(),
+        mp.output
+    );
+    save_font(&merged.font, &mp.output)?;
+    Ok(())
+}
+
+fn diff(dp: DiffParameter) -> Result<()> {
+    let old = open_font(&dp.old)?;
+    let new = open_font(&dp.new)?;
+    let differences = compare_fonts(&old, &new);
+    for difference in &differences {
+        println!("{}", difference);
+    }
+    let changed_chars: BTreeSet<u16> = differences.iter().map(|d| d.char_id()).collect();
+    println!(
+        "{} differences in {} characters",
+        differences.len(),
+        changed_chars.len()
+    );
+    if let Some(image_path) = &dp.image {
+        if differences.is_empty() {
+            println!("no image written, as the fonts have the same glyphs");
+        } else {
+            let image = render_differences(&old, &new, &differences);
+            let zoom = dp.zoom.max(1);
+            let image = resize(
+                &image,
+                image.width() * zoom,
+                image.height() * zoom,
+                FilterType::Nearest,
+            );
+            image
+                .save(image_path)
+                .with_context(|| format!("can't save the image at {:?}", image_path))?;
+        };
+    };
+    Ok(())
+}
+
+fn make_patch_folder(mp: MakePatchParameter) -> Result<()> {
+    let (base_dic, base_img) = read_base_font(&mp.base)?;
+    let modified = open_font(&mp.modified)?;
+    let patch = make_patch(&base_dic, &base_img, &modified)?;
+    println!(
+        "{} characters added or modified, {} removed",
+        patch.chars.len(),
+        patch.removed.len()
+    );
+    write_patch(&patch, &mp.output)?;
+    Ok(())
+}
 
+fn apply_patch_folder(ap: ApplyPatchParameter) -> Result<()> {
+    let (base_dic, base_img) = read_base_font(&ap.base)?;
+    let patch = read_patch(&ap.patch)?;
+    let font = apply_patch(&base_dic, &base_img, &patch)?;
+    println!("writing {} characters to {:?}", font.chars.len(), ap.output);
+    save_font(&font, &ap.output)?;
     Ok(())
 }
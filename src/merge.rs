@@ -0,0 +1,90 @@
+use crate::Font;
+use std::collections::BTreeSet;
+
+/// A font to take glyphs from with [`merge_fonts`]
+pub struct MergeSource<'a> {
+    pub font: &'a Font,
+    /// the characters to take from this font. Every character is taken if `None`.
+    pub chars: Option<&'a BTreeSet<char>>,
+}
+
+impl MergeSource<'_> {
+    fn provides(&self, char_id: u16) -> bool {
+        if !self.font.chars.contains_key(&char_id) {
+            return false;
+        };
+        match self.chars {
+            Some(chars) => {
+                std::char::from_u32(char_id as u32).is_some_and(|chara| chars.contains(&chara))
+            }
+            None => true,
+        }
+    }
+}
+
+/// A character provided by several sources with different glyphs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    pub char_id: u16,
+    /// the index of the sources providing this character, the glyph of the first one being used
+    pub sources: Vec<usize>,
+}
+
+/// The result of [`merge_fonts`]
+pub struct MergedFont {
+    pub font: Font,
+    /// the number of glyphs taken from each source
+    pub taken: Vec<usize>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Merge the glyphs of several fonts. When a character is provided by several sources, the glyph
+/// of the first one is used. Characters with identical glyphs in several sources aren't
+/// considered as conflicting.
+///
+/// The .dic header and image format are taken from the first source, as well as the atlas layout,
+/// which is only reused when the merged glyphs still fit in it.
+pub fn merge_fonts(sources: &[MergeSource]) -> MergedFont {
+    let mut font = match sources.first() {
+        Some(first) => Font {
+            chars: Default::default(),
+            ..first.font.clone()
+        },
+        None => Font::default(),
+    };
+    let mut taken = vec![0; sources.len()];
+    let mut conflicts = Vec::new();
+    let all_char_ids: BTreeSet<u16> = sources
+        .iter()
+        .flat_map(|source| source.font.chars.keys().copied())
+        .collect();
+    for char_id in all_char_ids {
+        let providing: Vec<usize> = sources
+            .iter()
+            .enumerate()
+            .filter(|(_, source)| source.provides(char_id))
+            .map(|(index, _)| index)
+            .collect();
+        let chosen = match providing.first() {
+            Some(chosen) => *chosen,
+            None => continue,
+        };
+        let char_data = &sources[chosen].font.chars[&char_id];
+        if providing[1..]
+            .iter()
+            .any(|other| &sources[*other].font.chars[&char_id] != char_data)
+        {
+            conflicts.push(MergeConflict {
+                char_id,
+                sources: providing,
+            });
+        };
+        taken[chosen] += 1;
+        font.chars.insert(char_id, char_data.clone());
+    }
+    MergedFont {
+        font,
+        taken,
+        conflicts,
+    }
+}
@@ -0,0 +1,82 @@
+use crate::FontToolError;
+use pmd_code_table::CodeToText;
+use pmd_farc::Farc;
+use pmd_message::MessageBin;
+use std::collections::BTreeSet;
+use std::io::{Cursor, Read};
+
+/// Return the characters displayed by a text in the format used by the translation tools, where
+/// control codes are written as `[tag]` placeholders, and `[` and `\` are escaped with a `\`.
+///
+/// Line breaks and other control characters are ignored.
+pub fn message_text_chars(text: &str) -> BTreeSet<char> {
+    let mut chars = BTreeSet::new();
+    let mut iterator = text.chars();
+    while let Some(chara) = iterator.next() {
+        match chara {
+            '[' => {
+                // skip the placeholder
+                for placeholder_char in &mut iterator {
+                    if placeholder_char == ']' {
+                        break;
+                    };
+                }
+            }
+            '\\' => {
+                if let Some(escaped) = iterator.next() {
+                    chars.insert(escaped);
+                };
+            }
+            chara if chara.is_control() => (),
+            chara => {
+                chars.insert(chara);
+            }
+        }
+    }
+    chars
+}
+
+/// Return the characters used in a game message file. It can be:
+/// - a FARC archive of message files, like `message_us.bin`
+/// - a single message file, stored in a SIR0 container
+/// - a UTF-8 text, like the one exported by translation tools
+///
+/// The control codes of binary message files are only recognized when a code table (read from the
+/// `code_table.bin` file of the game) is given. Otherwise, they are kept as characters.
+pub fn extract_message_chars(
+    data: &[u8],
+    code_to_text: Option<&CodeToText>,
+) -> Result<BTreeSet<char>, FontToolError> {
+    if data.starts_with(b"FARC") {
+        let farc = Farc::new(Cursor::new(data))
+            .map_err(|err| FontToolError::InvalidMessageFile(err.to_string()))?;
+        let mut chars = BTreeSet::new();
+        for hash in farc.iter_all_hash() {
+            let mut sub_file = Vec::new();
+            farc.get_hashed_file(*hash)
+                .map_err(|err| FontToolError::InvalidMessageFile(err.to_string()))?
+                .read_to_end(&mut sub_file)?;
+            chars.extend(extract_message_chars(&sub_file, code_to_text)?);
+        }
+        Ok(chars)
+    } else if data.starts_with(b"SIR0") {
+        let message = MessageBin::load_file(&mut Cursor::new(data), code_to_text)
+            .map_err(|err| FontToolError::InvalidMessageFile(err.to_string()))?;
+        let mut chars = BTreeSet::new();
+        for (_, _, text) in message.messages() {
+            if code_to_text.is_some() {
+                chars.extend(message_text_chars(text));
+            } else {
+                chars.extend(text.chars().filter(|chara| !chara.is_control()));
+            }
+        }
+        Ok(chars)
+    } else {
+        let text = String::from_utf8(data.to_vec()).map_err(|_| {
+            FontToolError::InvalidMessageFile(
+                "not a FARC archive, a SIR0 message file or a UTF-8 text".to_string(),
+            )
+        })?;
+        Ok(message_text_chars(text.trim_start_matches('\u{FEFF}')))
+    }
+}
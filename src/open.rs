@@ -0,0 +1,62 @@
+use crate::{
+    read_folder, read_sheet, read_text_font, write_folder, write_text_font, AtlasOptions, Font,
+    FontToolError,
+};
+use std::fs::{read_to_string, write, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+fn has_extension(path: &Path, expected: &str) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(expected))
+}
+
+/// Open a font, either from a folder in the format written by [`crate::write_folder`], from a
+/// .txt file written by [`crate::write_text_font`], from a .png grid sheet written by
+/// [`crate::write_sheet`], or from a .dic file, with the .img file with the same name next to it
+pub fn open_font(path: &Path) -> Result<Font, FontToolError> {
+    if path.is_dir() {
+        return read_folder(path);
+    };
+    if has_extension(path, "txt") {
+        let text = read_to_string(path)
+            .map_err(|err| FontToolError::FileIOError(err, path.to_path_buf()))?;
+        return read_text_font(&text);
+    };
+    if has_extension(path, "png") {
+        return read_sheet(path);
+    };
+    let img_path = path.with_extension("img");
+    let dic_file =
+        File::open(path).map_err(|err| FontToolError::FileIOError(err, path.to_path_buf()))?;
+    let img_file =
+        File::open(&img_path).map_err(|err| FontToolError::FileIOError(err, img_path.clone()))?;
+    Font::load(&mut BufReader::new(dic_file), &mut BufReader::new(img_file))
+}
+
+/// Save a font, as a .dic file and the .img file next to it if the path end with .dic, as a text
+/// font if it end with .txt, or as a folder otherwise
+pub fn save_font(font: &Font, path: &Path) -> Result<(), FontToolError> {
+    if has_extension(path, "txt") {
+        return write(path, write_text_font(font)?)
+            .map_err(|err| FontToolError::FileIOError(err, path.to_path_buf()));
+    };
+    if !has_extension(path, "dic") {
+        return write_folder(font, path);
+    };
+    let img_path = path.with_extension("img");
+    let mut dic_writer = BufWriter::new(
+        File::create(path).map_err(|err| FontToolError::FileIOError(err, path.to_path_buf()))?,
+    );
+    let mut img_writer = BufWriter::new(
+        File::create(&img_path).map_err(|err| FontToolError::FileIOError(err, img_path.clone()))?,
+    );
+    font.save(&mut dic_writer, &mut img_writer, &AtlasOptions::default())?;
+    dic_writer
+        .flush()
+        .map_err(|err| FontToolError::FileIOError(err, path.to_path_buf()))?;
+    img_writer
+        .flush()
+        .map_err(|err| FontToolError::FileIOError(err, img_path))?;
+    Ok(())
+}
@@ -0,0 +1,260 @@
+use crate::{CharData, Font, FontToolError};
+use flate2::Crc;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, File};
+use std::io::{BufReader, BufWriter, Cursor, Write};
+use std::path::{Path, PathBuf};
+
+/// The name of the file storing the content of a patch folder
+pub const PATCH_FILE_NAME: &str = "patch.json";
+
+/// A modified glyph of a [`FontPatch`]
+#[derive(Clone)]
+pub struct PatchChar {
+    pub xalign: i16,
+    pub yalign: i16,
+    pub distance: u16,
+    pub unk4: u16,
+    pub unk5: u16,
+    /// the new image of the glyph, if it changed
+    pub image: Option<RgbaImage>,
+}
+
+/// The differences between a base font, read from a .dic and .img file, and a modified font.
+///
+/// It only contain the glyphs that were added or modified, so it can be distributed without the
+/// base font.
+#[derive(Clone)]
+pub struct FontPatch {
+    /// the CRC32 of the .dic file of the base font
+    pub base_dic_crc32: u32,
+    /// the CRC32 of the .img file of the base font
+    pub base_img_crc32: u32,
+    /// the new unk1 value of the .dic header, if it changed
+    pub dic_unk1: Option<u32>,
+    /// the new unk2 value of the .dic header, if it changed
+    pub dic_unk2: Option<u32>,
+    /// the characters removed from the base font
+    pub removed: Vec<u16>,
+    /// the characters added to or modified from the base font
+    pub chars: BTreeMap<u16, PatchChar>,
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+/// Record the differences between the base font, stored in `base_dic` and `base_img`, and the
+/// modified font
+pub fn make_patch(
+    base_dic: &[u8],
+    base_img: &[u8],
+    modified: &Font,
+) -> Result<FontPatch, FontToolError> {
+    let base = Font::load(&mut Cursor::new(base_dic), &mut Cursor::new(base_img))?;
+    let mut patch = FontPatch {
+        base_dic_crc32: crc32(base_dic),
+        base_img_crc32: crc32(base_img),
+        dic_unk1: Some(modified.dic_unk1).filter(|unk1| *unk1 != base.dic_unk1),
+        dic_unk2: Some(modified.dic_unk2).filter(|unk2| *unk2 != base.dic_unk2),
+        removed: base
+            .chars
+            .keys()
+            .filter(|char_id| !modified.chars.contains_key(char_id))
+            .copied()
+            .collect(),
+        chars: BTreeMap::new(),
+    };
+    for (char_id, char_data) in &modified.chars {
+        let base_char = base.chars.get(char_id);
+        if base_char == Some(char_data) {
+            continue;
+        };
+        let image_changed = base_char.is_none_or(|base_char| base_char.image != char_data.image);
+        patch.chars.insert(
+            *char_id,
+            PatchChar {
+                xalign: char_data.xalign,
+                yalign: char_data.yalign,
+                distance: char_data.distance,
+                unk4: char_data.unk4,
+                unk5: char_data.unk5,
+                image: Some(char_data.image.clone()).filter(|_| image_changed),
+            },
+        );
+    }
+    Ok(patch)
+}
+
+/// Rebuild the modified font from the base font, stored in `base_dic` and `base_img`, and a patch.
+///
+/// Fail if the base font isn't the one the patch was made from. The atlas layout of the base font
+/// is kept when the modified glyphs still fit in it.
+pub fn apply_patch(
+    base_dic: &[u8],
+    base_img: &[u8],
+    patch: &FontPatch,
+) -> Result<Font, FontToolError> {
+    for (name, data, expected) in &[
+        (".dic", base_dic, patch.base_dic_crc32),
+        (".img", base_img, patch.base_img_crc32),
+    ] {
+        let actual = crc32(data);
+        if actual != *expected {
+            return Err(FontToolError::PatchBaseMismatch(name, *expected, actual));
+        };
+    }
+    let mut font = Font::load(&mut Cursor::new(base_dic), &mut Cursor::new(base_img))?;
+    if let Some(dic_unk1) = patch.dic_unk1 {
+        font.dic_unk1 = dic_unk1;
+    };
+    if let Some(dic_unk2) = patch.dic_unk2 {
+        font.dic_unk2 = dic_unk2;
+    };
+    for char_id in &patch.removed {
+        font.chars.remove(char_id);
+    }
+    for (char_id, patch_char) in &patch.chars {
+        let image = match (&patch_char.image, font.chars.get(char_id)) {
+            (Some(image), _) => image.clone(),
+            (None, Some(base_char)) => base_char.image.clone(),
+            (None, None) => {
+                return Err(FontToolError::InvalidPatch(format!(
+                    "the character {} isn't in the base font, but has no image",
+                    char_id
+                )))
+            }
+        };
+        let char_data = CharData::new(
+            *char_id,
+            image,
+            patch_char.xalign,
+            patch_char.yalign,
+            patch_char.distance,
+            patch_char.unk4,
+            patch_char.unk5,
+        )?;
+        font.chars.insert(*char_id, char_data);
+    }
+    Ok(font)
+}
+
+#[derive(Serialize, Deserialize)]
+struct PatchManifest {
+    base_dic_crc32: u32,
+    base_img_crc32: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dic_unk1: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dic_unk2: Option<u32>,
+    #[serde(default)]
+    removed: Vec<u16>,
+    chars: Vec<PatchManifestChar>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PatchManifestChar {
+    char: u16,
+    xalign: i16,
+    yalign: i16,
+    distance: u16,
+    unk4: u16,
+    unk5: u16,
+    /// path of the new glyph image, relative to the folder
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    image: Option<PathBuf>,
+}
+
+/// Write a patch to a folder, with a png file for each new glyph image
+pub fn write_patch(patch: &FontPatch, output: &Path) -> Result<(), FontToolError> {
+    create_dir_all(output).map_err(|err| FontToolError::FileIOError(err, output.to_path_buf()))?;
+    let mut manifest = PatchManifest {
+        base_dic_crc32: patch.base_dic_crc32,
+        base_img_crc32: patch.base_img_crc32,
+        dic_unk1: patch.dic_unk1,
+        dic_unk2: patch.dic_unk2,
+        removed: patch.removed.clone(),
+        chars: Vec::new(),
+    };
+    for (char_id, patch_char) in &patch.chars {
+        let image_path = match &patch_char.image {
+            Some(image) => {
+                let image_path = PathBuf::from(format!("{}.png", char_id));
+                let target_file = output.join(&image_path);
+                image
+                    .save(&target_file)
+                    .map_err(|err| FontToolError::ImageWriteError(err, target_file))?;
+                Some(image_path)
+            }
+            None => None,
+        };
+        manifest.chars.push(PatchManifestChar {
+            char: *char_id,
+            xalign: patch_char.xalign,
+            yalign: patch_char.yalign,
+            distance: patch_char.distance,
+            unk4: patch_char.unk4,
+            unk5: patch_char.unk5,
+            image: image_path,
+        });
+    }
+    let manifest_path = output.join(PATCH_FILE_NAME);
+    let manifest_file = File::create(&manifest_path)
+        .map_err(|err| FontToolError::FileIOError(err, manifest_path.clone()))?;
+    let mut manifest_writer = BufWriter::new(manifest_file);
+    serde_json::to_writer_pretty(&mut manifest_writer, &manifest)
+        .map_err(|err| FontToolError::ManifestWriteError(err, manifest_path.clone()))?;
+    manifest_writer
+        .flush()
+        .map_err(|err| FontToolError::FileIOError(err, manifest_path))?;
+    Ok(())
+}
+
+/// Read a patch folder written by [`write_patch`]
+pub fn read_patch(input: &Path) -> Result<FontPatch, FontToolError> {
+    let manifest_path = input.join(PATCH_FILE_NAME);
+    let manifest_file = File::open(&manifest_path)
+        .map_err(|err| FontToolError::FileIOError(err, manifest_path.clone()))?;
+    let manifest: PatchManifest = serde_json::from_reader(BufReader::new(manifest_file))
+        .map_err(|err| FontToolError::ManifestReadError(err, manifest_path.clone()))?;
+    let mut patch = FontPatch {
+        base_dic_crc32: manifest.base_dic_crc32,
+        base_img_crc32: manifest.base_img_crc32,
+        dic_unk1: manifest.dic_unk1,
+        dic_unk2: manifest.dic_unk2,
+        removed: manifest.removed,
+        chars: BTreeMap::new(),
+    };
+    for entry in manifest.chars {
+        let image = match &entry.image {
+            Some(image_path) => {
+                let image_path = input.join(image_path);
+                Some(
+                    image::open(&image_path)
+                        .map_err(|err| FontToolError::ImageReadError(err, image_path.clone()))?
+                        .to_rgba8(),
+                )
+            }
+            None => None,
+        };
+        let patch_char = PatchChar {
+            xalign: entry.xalign,
+            yalign: entry.yalign,
+            distance: entry.distance,
+            unk4: entry.unk4,
+            unk5: entry.unk5,
+            image,
+        };
+        if patch.chars.insert(entry.char, patch_char).is_some() {
+            return Err(FontToolError::InvalidPatch(format!(
+                "the character {} is present multiple time",
+                entry.char
+            )));
+        };
+    }
+    Ok(patch)
+}
@@ -0,0 +1,118 @@
+use crate::Font;
+use crate::{lookup_char, wrap_text};
+use image::{Pixel, Rgba, RgbaImage};
+use std::collections::BTreeMap;
+
+/// The parameters used to render a text with [`render_text`]
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// wrap the text so it fit in this width
+    pub box_width: Option<u32>,
+    /// the distance between two lines. Default to the highest bottom of the glyphs of the font.
+    pub line_height: Option<u32>,
+    pub color: Rgba<u8>,
+    pub background: Rgba<u8>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            box_width: None,
+            line_height: None,
+            color: Rgba([255, 255, 255, 255]),
+            background: Rgba([0, 0, 0, 255]),
+        }
+    }
+}
+
+/// The result of [`render_text`]
+pub struct RenderedText {
+    pub image: RgbaImage,
+    /// the characters of the text that aren't in the font
+    pub missing_chars: Vec<char>,
+}
+
+/// The default distance between two lines of the font
+pub fn default_line_height(font: &Font) -> u32 {
+    font.chars
+        .values()
+        .map(|c| (c.yalign as i32 + c.glyth_height as i32).max(0) as u32)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Guess the distance between the top of a line and the baseline, as the most common bottom of
+/// the glyphs (most of them lay on the baseline)
+pub fn estimate_baseline(font: &Font) -> u32 {
+    let mut bottoms: BTreeMap<u32, usize> = BTreeMap::new();
+    for char_data in font.chars.values() {
+        let bottom = (char_data.yalign as i32 + char_data.glyth_height as i32).max(0) as u32;
+        *bottoms.entry(bottom).or_default() += 1;
+    }
+    bottoms
+        .into_iter()
+        .max_by_key(|(bottom, count)| (*count, *bottom))
+        .map(|(bottom, _)| bottom)
+        .unwrap_or(0)
+}
+
+/// Render a text with the font. Each glyph is drawn at the pen position moved by its xalign
+/// and yalign, then the pen advance by its distance. Only the alpha of the glyphs is used, and
+/// they are drawn with the color of the option.
+pub fn render_text(font: &Font, text: &str, options: &RenderOptions) -> RenderedText {
+    let line_height = options
+        .line_height
+        .unwrap_or_else(|| default_line_height(font)) as i32;
+    let mut missing_chars = Vec::new();
+
+    // the position of every glyph to draw
+    let mut placements = Vec::new();
+    let mut right = options.box_width.unwrap_or(0) as i32;
+    let mut bottom = 0;
+    let mut left = 0;
+    let mut top = 0;
+    for (line_index, line) in wrap_text(font, text, options.box_width).iter().enumerate() {
+        let mut pen_x = 0;
+        let pen_y = line_index as i32 * line_height;
+        for chara in line.chars() {
+            let char_data = match lookup_char(font, chara) {
+                Some(char_id) => &font.chars[&char_id],
+                None => {
+                    if !missing_chars.contains(&chara) {
+                        missing_chars.push(chara);
+                    };
+                    continue;
+                }
+            };
+            let x = pen_x + char_data.xalign as i32;
+            let y = pen_y + char_data.yalign as i32;
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x + char_data.glyth_width as i32);
+            bottom = bottom.max(y + char_data.glyth_height as i32);
+            placements.push((x, y, char_data));
+            pen_x += char_data.distance as i32;
+        }
+        right = right.max(pen_x);
+        bottom = bottom.max(pen_y + line_height);
+    }
+
+    let mut image = RgbaImage::from_pixel(
+        (right - left).max(1) as u32,
+        (bottom - top).max(1) as u32,
+        options.background,
+    );
+    for (x, y, char_data) in placements {
+        for (glyph_x, glyph_y, pixel) in char_data.image.enumerate_pixels() {
+            let mut color = options.color;
+            color[3] = (color[3] as u16 * pixel[3] as u16 / 255) as u8;
+            image
+                .get_pixel_mut((x - left) as u32 + glyph_x, (y - top) as u32 + glyph_y)
+                .blend(&color);
+        }
+    }
+    RenderedText {
+        image,
+        missing_chars,
+    }
+}
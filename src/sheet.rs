@@ -0,0 +1,291 @@
+use crate::folder::{char_comment, glyph_order};
+use crate::{AtlasLayout, CharData, Font, FontToolError, GlyphPosition, ImgFormat};
+use image::{GenericImage, GenericImageView, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fs::{read_to_string, write};
+use std::path::{Path, PathBuf};
+
+/// The color of the lines separating the cells of a sheet
+const GRID_COLOR: Rgba<u8> = Rgba([255, 0, 255, 255]);
+
+#[derive(Serialize, Deserialize)]
+struct Sidecar {
+    dic_unk1: u32,
+    dic_unk2: u32,
+    #[serde(default)]
+    img_format: ImgFormat,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    atlas_width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    atlas_height: Option<u32>,
+    /// the size of a cell, without the grid lines
+    cell_width: u32,
+    cell_height: u32,
+    /// the position of the pen in each cell, the glyph being drawn at its xalign and yalign from it
+    origin_x: u32,
+    origin_y: u32,
+    /// the glyphs, in the order they are written in the .dic file
+    chars: Vec<SidecarChar>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SidecarChar {
+    char: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+    column: u32,
+    row: u32,
+    /// the box of the glyph when the sheet was exported, kept when the drawing still fit in it
+    xalign: i16,
+    yalign: i16,
+    width: u16,
+    height: u16,
+    distance: u16,
+    unk4: u16,
+    unk5: u16,
+    /// position of the glyph in the atlas of the original .img file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    x: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    y: Option<u16>,
+}
+
+/// A font drawn on a grid sheet, with [`export_sheet`]
+pub struct SheetExport {
+    pub image: RgbaImage,
+    /// the content of the JSON sidecar, that store which character is in each cell and their
+    /// metrics
+    pub sidecar: String,
+}
+
+/// The position of the top left pixel of a cell, or None if it can't be addressed
+fn cell_position(column: u32, row: u32, cell_width: u32, cell_height: u32) -> Option<(u32, u32)> {
+    let position = |index: u32, size: u32| size.checked_add(1)?.checked_mul(index)?.checked_add(1);
+    Some((position(column, cell_width)?, position(row, cell_height)?))
+}
+
+/// Draw every glyph of the font in a grid of cells of the same size, `columns` cells wide.
+///
+/// All the glyphs are placed relative to the same pen position in their cell, so they are
+/// aligned with each other. The cells are separated by magenta lines.
+pub fn export_sheet(font: &Font, columns: u32) -> Result<SheetExport, FontToolError> {
+    let columns = columns.max(1);
+    let (mut left, mut top, mut right, mut bottom) = (0, 0, 1, 1);
+    for char_data in font.chars.values() {
+        left = left.min(char_data.xalign as i32);
+        top = top.min(char_data.yalign as i32);
+        right = right
+            .max(char_data.xalign as i32 + char_data.glyth_width as i32)
+            .max(char_data.distance as i32);
+        bottom = bottom.max(char_data.yalign as i32 + char_data.glyth_height as i32);
+    }
+    let cell_width = (right - left) as u32;
+    let cell_height = (bottom - top) as u32;
+    let rows = (font.chars.len() as u32).div_ceil(columns).max(1);
+
+    // the sheet ends where the cell after the last one would start
+    let (sheet_width, sheet_height) = cell_position(columns, rows, cell_width, cell_height)
+        .ok_or_else(|| FontToolError::InvalidSheet("the sheet is too big".into()))?;
+    let mut image = RgbaImage::from_pixel(sheet_width, sheet_height, GRID_COLOR);
+    let mut sidecar = Sidecar {
+        dic_unk1: font.dic_unk1,
+        dic_unk2: font.dic_unk2,
+        img_format: font.img_format,
+        atlas_width: font.original_layout.as_ref().map(|l| l.width),
+        atlas_height: font.original_layout.as_ref().map(|l| l.height),
+        cell_width,
+        cell_height,
+        origin_x: -left as u32,
+        origin_y: -top as u32,
+        chars: Vec::new(),
+    };
+    for (index, (char_id, position)) in glyph_order(font).into_iter().enumerate() {
+        let char_data = &font.chars[&char_id];
+        let column = index as u32 % columns;
+        let row = index as u32 / columns;
+        let (cell_x, cell_y) = cell_position(column, row, cell_width, cell_height)
+            .ok_or_else(|| FontToolError::InvalidSheet("the sheet is too big".into()))?;
+        for y in 0..cell_height {
+            for x in 0..cell_width {
+                image.put_pixel(cell_x + x, cell_y + y, Rgba([0, 0, 0, 0]));
+            }
+        }
+        image
+            .copy_from(
+                &char_data.image,
+                cell_x + (char_data.xalign as i32 - left) as u32,
+                cell_y + (char_data.yalign as i32 - top) as u32,
+            )
+            .map_err(|_| {
+                FontToolError::InvalidSheet(format!("can't draw the glyph {}", char_id))
+            })?;
+        sidecar.chars.push(SidecarChar {
+            char: char_id,
+            comment: char_comment(char_id),
+            column,
+            row,
+            xalign: char_data.xalign,
+            yalign: char_data.yalign,
+            width: char_data.glyth_width,
+            height: char_data.glyth_height,
+            distance: char_data.distance,
+            unk4: char_data.unk4,
+            unk5: char_data.unk5,
+            x: position.map(|p| p.0),
+            y: position.map(|p| p.1),
+        });
+    }
+    let sidecar = serde_json::to_string_pretty(&sidecar)
+        .map_err(|err| FontToolError::InvalidSheet(err.to_string()))?;
+    Ok(SheetExport { image, sidecar })
+}
+
+/// Cut a sheet written by [`export_sheet`] back into glyphs.
+///
+/// Each glyph is trimmed to the visible pixels of its cell, with its xalign and yalign computed
+/// from their position relative to the pen. When the drawing still fit in the box of the glyph
+/// recorded in the sidecar, this box is kept, so the unmodified glyphs are identical to the
+/// original ones.
+pub fn import_sheet(image: &RgbaImage, sidecar: &str) -> Result<Font, FontToolError> {
+    let sidecar: Sidecar = serde_json::from_str(sidecar)
+        .map_err(|err| FontToolError::InvalidSheet(err.to_string()))?;
+    let mut font = Font {
+        dic_unk1: sidecar.dic_unk1,
+        dic_unk2: sidecar.dic_unk2,
+        img_format: sidecar.img_format,
+        ..Font::default()
+    };
+    let mut layout = match (sidecar.atlas_width, sidecar.atlas_height) {
+        (Some(width), Some(height)) => Some(AtlasLayout {
+            width,
+            height,
+            glyphs: Vec::new(),
+        }),
+        _ => None,
+    };
+    for entry in &sidecar.chars {
+        let outside = || {
+            FontToolError::InvalidSheet(format!(
+                "the cell of the character {} is outside of the sheet",
+                entry.char
+            ))
+        };
+        let (cell_x, cell_y) = cell_position(
+            entry.column,
+            entry.row,
+            sidecar.cell_width,
+            sidecar.cell_height,
+        )
+        .ok_or_else(outside)?;
+        let fits = |start: u32, size: u32, limit: u32| {
+            start.checked_add(size).is_some_and(|end| end <= limit)
+        };
+        if !fits(cell_x, sidecar.cell_width, image.width())
+            || !fits(cell_y, sidecar.cell_height, image.height())
+        {
+            return Err(outside());
+        };
+        let cell = image.view(cell_x, cell_y, sidecar.cell_width, sidecar.cell_height);
+
+        // the bounding box of the visible pixels, as (left, top, right, bottom)
+        let mut drawn: Option<(u32, u32, u32, u32)> = None;
+        for (x, y, pixel) in cell.pixels() {
+            if pixel[3] != 0 {
+                drawn = Some(match drawn {
+                    Some((left, top, right, bottom)) => {
+                        (left.min(x), top.min(y), right.max(x + 1), bottom.max(y + 1))
+                    }
+                    None => (x, y, x + 1, y + 1),
+                });
+            };
+        }
+        let recorded_left = sidecar.origin_x as i64 + entry.xalign as i64;
+        let recorded_top = sidecar.origin_y as i64 + entry.yalign as i64;
+        let recorded_right = recorded_left + entry.width as i64;
+        let recorded_bottom = recorded_top + entry.height as i64;
+        let recorded_fits = recorded_left >= 0
+            && recorded_top >= 0
+            && recorded_right <= sidecar.cell_width as i64
+            && recorded_bottom <= sidecar.cell_height as i64;
+        let (left, top, right, bottom) = match drawn {
+            Some((left, top, right, bottom))
+                if !recorded_fits
+                    || (left as i64) < recorded_left
+                    || (top as i64) < recorded_top
+                    || (right as i64) > recorded_right
+                    || (bottom as i64) > recorded_bottom =>
+            {
+                (left, top, right, bottom)
+            }
+            _ if recorded_fits => (
+                recorded_left as u32,
+                recorded_top as u32,
+                recorded_right as u32,
+                recorded_bottom as u32,
+            ),
+            _ => (0, 0, 0, 0),
+        };
+        let glyph = cell.view(left, top, right - left, bottom - top).to_image();
+        let align = |position: u32, origin: u32| {
+            i16::try_from(position as i64 - origin as i64).map_err(|_| {
+                FontToolError::InvalidSheet(format!(
+                    "the glyph of the character {} is too far from the pen",
+                    entry.char
+                ))
+            })
+        };
+        let char_data = CharData::new(
+            entry.char,
+            glyph,
+            align(left, sidecar.origin_x)?,
+            align(top, sidecar.origin_y)?,
+            entry.distance,
+            entry.unk4,
+            entry.unk5,
+        )?;
+        if font.chars.insert(entry.char, char_data).is_some() {
+            return Err(FontToolError::InvalidSheet(format!(
+                "the character {} is present multiple time",
+                entry.char
+            )));
+        };
+        if let (Some(layout), Some(x), Some(y)) = (&mut layout, entry.x, entry.y) {
+            layout.glyphs.push(GlyphPosition {
+                char_id: entry.char,
+                x,
+                y,
+            });
+        };
+    }
+    font.original_layout = layout;
+    Ok(font)
+}
+
+fn sidecar_path(sheet_path: &Path) -> PathBuf {
+    sheet_path.with_extension("json")
+}
+
+/// Write a font as a png grid sheet, with its JSON sidecar next to it (with the same name, but a
+/// .json extension)
+pub fn write_sheet(font: &Font, path: &Path, columns: u32) -> Result<(), FontToolError> {
+    let export = export_sheet(font, columns)?;
+    export
+        .image
+        .save(path)
+        .map_err(|err| FontToolError::ImageWriteError(err, path.to_path_buf()))?;
+    let sidecar_path = sidecar_path(path);
+    write(&sidecar_path, export.sidecar)
+        .map_err(|err| FontToolError::FileIOError(err, sidecar_path))
+}
+
+/// Read a png grid sheet written by [`write_sheet`], and its sidecar
+pub fn read_sheet(path: &Path) -> Result<Font, FontToolError> {
+    let image = image::open(path)
+        .map_err(|err| FontToolError::ImageReadError(err, path.to_path_buf()))?
+        .to_rgba8();
+    let sidecar_path = sidecar_path(path);
+    let sidecar = read_to_string(&sidecar_path)
+        .map_err(|err| FontToolError::FileIOError(err, sidecar_path))?;
+    import_sheet(&image, &sidecar)
+}
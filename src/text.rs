@@ -0,0 +1,63 @@
+use crate::Font;
+use std::convert::TryInto;
+
+/// Return the id used in the .dic file for this character, if the font contain it
+pub fn lookup_char(font: &Font, chara: char) -> Option<u16> {
+    let char_id: u16 = (chara as u32).try_into().ok()?;
+    if font.chars.contains_key(&char_id) {
+        Some(char_id)
+    } else {
+        None
+    }
+}
+
+/// The width in pixel of a line of text, as the sum of the distance of every character.
+/// Characters that aren't in the font are ignored, and returned in the second value.
+pub fn measure_line(font: &Font, line: &str) -> (u32, Vec<char>) {
+    let mut width = 0;
+    let mut missing = Vec::new();
+    for chara in line.chars() {
+        match lookup_char(font, chara) {
+            Some(char_id) => width += font.chars[&char_id].distance as u32,
+            None => missing.push(chara),
+        }
+    }
+    (width, missing)
+}
+
+/// Split a text into lines, at the newlines and, if `box_width` is set, between words so each
+/// line fit in this width (a word wider than the box is split between characters).
+pub fn wrap_text(font: &Font, text: &str, box_width: Option<u32>) -> Vec<String> {
+    let box_width = match box_width {
+        Some(box_width) => box_width,
+        None => return text.split('\n').map(|line| line.to_string()).collect(),
+    };
+    let width = |text: &str| measure_line(font, text).0;
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut current_line = String::new();
+        for word in paragraph.split(' ') {
+            let candidate = if current_line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", current_line, word)
+            };
+            if width(&candidate) <= box_width {
+                current_line = candidate;
+                continue;
+            }
+            if !current_line.is_empty() {
+                lines.push(std::mem::take(&mut current_line));
+            }
+            for chara in word.chars() {
+                current_line.push(chara);
+                if width(&current_line) > box_width && current_line.chars().count() > 1 {
+                    current_line.pop();
+                    lines.push(std::mem::replace(&mut current_line, chara.to_string()));
+                }
+            }
+        }
+        lines.push(current_line);
+    }
+    lines
+}
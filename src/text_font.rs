@@ -0,0 +1,292 @@
+use crate::folder::{char_comment, glyph_order};
+use crate::{AtlasLayout, CharData, Font, FontToolError, GlyphPosition, ImgFormat};
+use image::{Rgba, RgbaImage};
+use std::fmt::Write;
+use std::str::{FromStr, SplitWhitespace};
+
+const TEXT_FONT_HEADER: &str = "# pmdfonttool text font";
+/// the character used for the fully transparent pixels of the glyph art
+const TRANSPARENT_PIXEL: char = '.';
+
+pub(crate) fn luminance_of(pixel: &Rgba<u8>) -> u8 {
+    ((pixel[0] as u16 + pixel[1] as u16 + pixel[2] as u16) / 3).min(15) as u8
+}
+
+pub(crate) fn hex_digit(value: u8) -> char {
+    std::char::from_digit(value as u32, 16)
+        .expect("value out of the hexadecimal digit range")
+        .to_ascii_uppercase()
+}
+
+/// Write a font as a text, where each glyph is drawn with a character per pixel, so the changes to
+/// the glyphs can be reviewed as text.
+///
+/// The alpha of each pixel is written as an hexadecimal digit (with `.` for transparent pixels),
+/// and its luminance as a single value for the glyph when every pixel has the same, or as a
+/// second grid otherwise. Both are stored with 4 bits, like in the .img file.
+pub fn write_text_font(font: &Font) -> Result<String, FontToolError> {
+    let mut text = String::new();
+    writeln!(text, "{}", TEXT_FONT_HEADER)?;
+    writeln!(text, "dic_unk1 {}", font.dic_unk1)?;
+    writeln!(text, "dic_unk2 {}", font.dic_unk2)?;
+    writeln!(text, "img_format {}", font.img_format)?;
+    if let Some(layout) = &font.original_layout {
+        writeln!(text, "atlas {} {}", layout.width, layout.height)?;
+    };
+    for (char_id, position) in glyph_order(font) {
+        let char_data = &font.chars[&char_id];
+        writeln!(text)?;
+        match char_comment(char_id) {
+            Some(comment) => writeln!(text, "char {} '{}'", char_id, comment)?,
+            None => writeln!(text, "char {}", char_id)?,
+        };
+        writeln!(
+            text,
+            "width {} height {} xalign {} yalign {} distance {} unk4 {} unk5 {}",
+            char_data.glyth_width,
+            char_data.glyth_height,
+            char_data.xalign,
+            char_data.yalign,
+            char_data.distance,
+            char_data.unk4,
+            char_data.unk5
+        )?;
+        if let Some((x, y)) = position {
+            writeln!(text, "position {} {}", x, y)?;
+        };
+        for row in char_data.image.rows() {
+            text.push('|');
+            for pixel in row {
+                let alpha = pixel[3] >> 4;
+                text.push(if alpha == 0 {
+                    TRANSPARENT_PIXEL
+                } else {
+                    hex_digit(alpha)
+                });
+            }
+            text.push_str("|\n");
+        }
+        let mut luminances = char_data.image.pixels().map(luminance_of);
+        let first_luminance = luminances.next().unwrap_or(0);
+        if luminances.all(|luminance| luminance == first_luminance) {
+            writeln!(text, "luminance {}", hex_digit(first_luminance))?;
+        } else {
+            writeln!(text, "luminance")?;
+            for row in char_data.image.rows() {
+                text.push('|');
+                for pixel in row {
+                    text.push(hex_digit(luminance_of(pixel)));
+                }
+                text.push_str("|\n");
+            }
+        };
+    }
+    Ok(text)
+}
+
+fn parse_next<T: FromStr>(
+    parts: &mut SplitWhitespace,
+    name: &str,
+    line_number: usize,
+) -> Result<T, FontToolError> {
+    parts
+        .next()
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| {
+            FontToolError::InvalidTextFont(line_number, format!("expected a number for {}", name))
+        })
+}
+
+/// Parse a metric of a glyph, preceded by its name
+fn parse_metric<T: FromStr>(
+    parts: &mut SplitWhitespace,
+    name: &str,
+    line_number: usize,
+) -> Result<T, FontToolError> {
+    if parts.next() != Some(name) {
+        return Err(FontToolError::InvalidTextFont(
+            line_number,
+            format!("expected the {} of the glyph", name),
+        ));
+    };
+    parse_next(parts, name, line_number)
+}
+
+#[derive(Clone, Copy)]
+struct TextMetrics {
+    width: u16,
+    height: u16,
+    xalign: i16,
+    yalign: i16,
+    distance: u16,
+    unk4: u16,
+    unk5: u16,
+}
+
+struct TextGlyph {
+    line: usize,
+    char_id: u16,
+    metrics: Option<TextMetrics>,
+    position: Option<(u16, u16)>,
+    alpha_rows: Vec<Vec<u8>>,
+    luminance: Option<u8>,
+    luminance_rows: Vec<Vec<u8>>,
+    reading_luminance: bool,
+}
+
+/// Read a font written by [`write_text_font`]
+pub fn read_text_font(text: &str) -> Result<Font, FontToolError> {
+    let mut font = Font::default();
+    let mut layout = None;
+    let mut glyphs: Vec<TextGlyph> = Vec::new();
+    for (line_index, line) in text.lines().enumerate() {
+        let line_number = line_index + 1;
+        let invalid = |message: &str| FontToolError::InvalidTextFont(line_number, message.into());
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        };
+        if let Some(row) = line.strip_prefix('|') {
+            let glyph = glyphs
+                .last_mut()
+                .ok_or_else(|| invalid("a glyph row is outside of a glyph"))?;
+            let row = row
+                .strip_suffix('|')
+                .ok_or_else(|| invalid("a glyph row should end with |"))?;
+            let mut values = Vec::new();
+            for chara in row.chars() {
+                values.push(match chara {
+                    TRANSPARENT_PIXEL if !glyph.reading_luminance => 0,
+                    chara => chara
+                        .to_digit(16)
+                        .ok_or_else(|| invalid("a pixel should be an hexadecimal digit"))?
+                        as u8,
+                });
+            }
+            if glyph.reading_luminance {
+                glyph.luminance_rows.push(values);
+            } else {
+                glyph.alpha_rows.push(values);
+            };
+            continue;
+        };
+        let mut parts = line.split_whitespace();
+        let keyword = parts.next().unwrap_or_default();
+        match keyword {
+            "dic_unk1" => font.dic_unk1 = parse_next(&mut parts, keyword, line_number)?,
+            "dic_unk2" => font.dic_unk2 = parse_next(&mut parts, keyword, line_number)?,
+            "img_format" => {
+                let format = parts.next().unwrap_or_default();
+                font.img_format = ImgFormat::from_str(format).map_err(|err| invalid(&err))?;
+            }
+            "atlas" => {
+                layout = Some(AtlasLayout {
+                    width: parse_next(&mut parts, "the atlas width", line_number)?,
+                    height: parse_next(&mut parts, "the atlas height", line_number)?,
+                    glyphs: Vec::new(),
+                })
+            }
+            "char" => glyphs.push(TextGlyph {
+                line: line_number,
+                char_id: parse_next(&mut parts, "the character id", line_number)?,
+                metrics: None,
+                position: None,
+                alpha_rows: Vec::new(),
+                luminance: None,
+                luminance_rows: Vec::new(),
+                reading_luminance: false,
+            }),
+            "width" => {
+                let glyph = glyphs
+                    .last_mut()
+                    .ok_or_else(|| invalid("metrics are outside of a glyph"))?;
+                // the keyword of the first metric was already read
+                let metrics = TextMetrics {
+                    width: parse_next(&mut parts, "width", line_number)?,
+                    height: parse_metric(&mut parts, "height", line_number)?,
+                    xalign: parse_metric(&mut parts, "xalign", line_number)?,
+                    yalign: parse_metric(&mut parts, "yalign", line_number)?,
+                    distance: parse_metric(&mut parts, "distance", line_number)?,
+                    unk4: parse_metric(&mut parts, "unk4", line_number)?,
+                    unk5: parse_metric(&mut parts, "unk5", line_number)?,
+                };
+                glyph.metrics = Some(metrics);
+            }
+            "position" => {
+                let x = parse_next(&mut parts, "x", line_number)?;
+                let y = parse_next(&mut parts, "y", line_number)?;
+                glyphs
+                    .last_mut()
+                    .ok_or_else(|| invalid("a position is outside of a glyph"))?
+                    .position = Some((x, y));
+            }
+            "luminance" => {
+                let value = match parts.next() {
+                    Some(value) => Some(
+                        u8::from_str_radix(value, 16)
+                            .ok()
+                            .filter(|value| *value < 16)
+                            .ok_or_else(|| {
+                                invalid("the luminance should be an hexadecimal digit")
+                            })?,
+                    ),
+                    None => None,
+                };
+                let glyph = glyphs
+                    .last_mut()
+                    .ok_or_else(|| invalid("a luminance is outside of a glyph"))?;
+                glyph.luminance = value;
+                glyph.reading_luminance = value.is_none();
+            }
+            _ => return Err(invalid(&format!("unknown keyword {:?}", keyword))),
+        };
+    }
+
+    for glyph in glyphs {
+        let invalid = |message: &str| FontToolError::InvalidTextFont(glyph.line, message.into());
+        let metrics = glyph
+            .metrics
+            .ok_or_else(|| invalid("the glyph doesn't have metrics"))?;
+        let (width, height) = (metrics.width as u32, metrics.height as u32);
+        let has_size = |rows: &Vec<Vec<u8>>| {
+            rows.len() == height as usize && rows.iter().all(|row| row.len() == width as usize)
+        };
+        if !has_size(&glyph.alpha_rows) {
+            return Err(invalid("the glyph art doesn't have the size of the glyph"));
+        };
+        if glyph.luminance.is_none() && !has_size(&glyph.luminance_rows) {
+            return Err(invalid(
+                "the luminance grid doesn't have the size of the glyph",
+            ));
+        };
+        let image = RgbaImage::from_fn(width, height, |x, y| {
+            let luminance = match glyph.luminance {
+                Some(luminance) => luminance,
+                None => glyph.luminance_rows[y as usize][x as usize],
+            };
+            let alpha = glyph.alpha_rows[y as usize][x as usize] << 4;
+            Rgba([luminance, luminance, luminance, alpha])
+        });
+        let char_data = CharData::new(
+            glyph.char_id,
+            image,
+            metrics.xalign,
+            metrics.yalign,
+            metrics.distance,
+            metrics.unk4,
+            metrics.unk5,
+        )?;
+        if font.chars.insert(glyph.char_id, char_data).is_some() {
+            return Err(invalid("the character is present multiple time"));
+        };
+        if let (Some(layout), Some((x, y))) = (&mut layout, glyph.position) {
+            layout.glyphs.push(GlyphPosition {
+                char_id: glyph.char_id,
+                x,
+                y,
+            });
+        };
+    }
+    font.original_layout = layout;
+    Ok(font)
+}
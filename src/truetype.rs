@@ -0,0 +1,239 @@
+use crate::{CharData, Font, FontToolError, UnkValues};
+use fontdue::FontSettings;
+use image::{ImageBuffer, Rgba};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+use std::str::FromStr;
+
+/// Read a list of character from an UTF-8 text. A character can be present multiple time.
+pub fn read_char_list<R: Read>(reader: &mut R) -> Result<BTreeSet<char>, FontToolError> {
+    let mut char_list_buffer = Vec::new();
+    reader.read_to_end(&mut char_list_buffer)?;
+    let char_list_string = String::from_utf8(char_list_buffer)?;
+    Ok(char_list_string.chars().collect())
+}
+
+/// What to do with a character that isn't in any of the TrueType fonts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingGlyph {
+    /// fail with an error listing every missing character
+    Fail,
+    /// don't include the character in the generated font
+    Skip,
+    /// include the .notdef glyph of the first font
+    Notdef,
+}
+
+impl FromStr for MissingGlyph {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(Self::Fail),
+            "skip" => Ok(Self::Skip),
+            "notdef" => Ok(Self::Notdef),
+            _ => Err(format!(
+                "unknown missing glyph policy {:?} (expected fail, skip or notdef)",
+                s
+            )),
+        }
+    }
+}
+
+/// The parameters used to rasterize a TrueType font with [`import_truetype`]
+#[derive(Debug, Clone)]
+pub struct TrueTypeOptions {
+    /// the height of the generated font, in pixel
+    pub scale: u16,
+    /// the distance between the top of the line and the baseline, in pixel. Default to the ascent
+    /// of the font.
+    pub baseline: Option<i32>,
+    /// the height of a line, in pixel. Default to the ascent minus the descent of the font.
+    pub line_height: Option<u32>,
+    /// what to do with the characters that aren't in any font
+    pub missing: MissingGlyph,
+    /// the unk4 and unk5 values of the glyphs
+    pub unk: UnkValues,
+}
+
+impl Default for TrueTypeOptions {
+    fn default() -> Self {
+        Self {
+            scale: 18,
+            baseline: None,
+            line_height: None,
+            missing: MissingGlyph::Skip,
+            unk: UnkValues::default(),
+        }
+    }
+}
+
+/// A TrueType font to take glyphs from, with the adjustments to apply to its glyphs
+#[derive(Debug, Clone, Copy)]
+pub struct TrueTypeSource<'a> {
+    /// the content of the TrueType font file
+    pub bytes: &'a [u8],
+    /// the height this font is rasterized at, in pixel. Default to the scale of the options.
+    pub scale: Option<u16>,
+    /// moves the glyphs of this font to the right, in pixel
+    pub x_offset: i16,
+    /// moves the glyphs of this font down, in pixel
+    pub y_offset: i16,
+}
+
+impl<'a> TrueTypeSource<'a> {
+    /// A font without adjustment
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            scale: None,
+            x_offset: 0,
+            y_offset: 0,
+        }
+    }
+}
+
+/// A glyph that doesn't entirely fit in the line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClippedGlyph {
+    pub char_id: u16,
+    pub chara: char,
+    /// the number of pixel above the top of the line
+    pub above: u32,
+    /// the number of pixel below the bottom of the line
+    pub below: u32,
+}
+
+/// The result of [`import_truetype`]
+pub struct TrueTypeImport {
+    pub font: Font,
+    /// the baseline used, in pixel from the top of the line
+    pub baseline: i32,
+    /// the height of a line used, in pixel
+    pub line_height: u32,
+    /// the glyphs that go above or below the line
+    pub clipped: Vec<ClippedGlyph>,
+    /// the characters that weren't found in the first font, with the index of the fallback font
+    /// they were taken from
+    pub from_fallback: Vec<(u16, char, usize)>,
+    /// the characters that weren't found in any font
+    pub missing: Vec<(u16, char)>,
+}
+
+/// Rasterize the characters of TrueType fonts. `chars_to_include` associate the id of the
+/// character in the game with the character of the TrueType font (see [`crate::map_chars`]).
+///
+/// Each character is taken from the first font of `sources` that contains it, the following
+/// ones being fallbacks. The glyphs of every font are aligned on a common baseline, placed by
+/// default at the ascent given by the horizontal line metrics of the first font.
+pub fn import_truetype(
+    sources: &[TrueTypeSource],
+    chars_to_include: &BTreeMap<u16, char>,
+    options: &TrueTypeOptions,
+) -> Result<TrueTypeImport, FontToolError> {
+    let scale = options.scale as f32;
+    let ttf_fonts = sources
+        .iter()
+        .map(|source| {
+            let source_scale = source.scale.map_or(scale, |s| s as f32);
+            let ttf_font = fontdue::Font::from_bytes(
+                source.bytes,
+                FontSettings {
+                    scale: source_scale,
+                    ..Default::default()
+                },
+            )
+            .map_err(FontToolError::TrueTypeParseError)?;
+            Ok((ttf_font, source_scale, source))
+        })
+        .collect::<Result<Vec<_>, FontToolError>>()?;
+    let primary_font = ttf_fonts
+        .first()
+        .ok_or(FontToolError::TrueTypeParseError("no font was given"))?;
+
+    let line_metrics = primary_font.0.horizontal_line_metrics(primary_font.1);
+    let baseline = options.baseline.unwrap_or_else(|| match line_metrics {
+        Some(line_metrics) => line_metrics.ascent.round() as i32,
+        None => options.scale as i32,
+    });
+    let line_height = options.line_height.unwrap_or_else(|| match line_metrics {
+        Some(line_metrics) => (line_metrics.ascent - line_metrics.descent).round() as u32,
+        None => options.scale as u32,
+    });
+
+    let mut font = Font::default();
+    let mut clipped = Vec::new();
+    let mut from_fallback = Vec::new();
+    let mut missing = Vec::new();
+    for (char_id, chara) in chars_to_include {
+        // the glyph 0 is the .notdef glyph, used for characters absent from the font
+        let (ttf_font, source_scale, source) = match ttf_fonts
+            .iter()
+            .position(|(ttf_font, _, _)| ttf_font.lookup_glyph_index(*chara) != 0)
+        {
+            Some(font_index) => {
+                if font_index != 0 {
+                    from_fallback.push((*char_id, *chara, font_index));
+                };
+                &ttf_fonts[font_index]
+            }
+            None => {
+                missing.push((*char_id, *chara));
+                match options.missing {
+                    MissingGlyph::Notdef => primary_font,
+                    MissingGlyph::Fail | MissingGlyph::Skip => continue,
+                }
+            }
+        };
+        let (metric, bitmap_luminance) = ttf_font.rasterize(*chara, *source_scale);
+        let char_image: ImageBuffer<Rgba<u8>, Vec<_>> = if metric.width != 0 && metric.height != 0 {
+            let mut bitmap: Vec<u8> = Vec::new();
+            for pixel in bitmap_luminance.into_iter() {
+                bitmap.extend_from_slice(&[0, 0, 0, pixel]);
+            }
+            ImageBuffer::from_vec(metric.width as u32, metric.height as u32, bitmap)
+                .expect("fontdue returned a bitmap of the wrong size")
+        } else {
+            ImageBuffer::new(1, 1)
+        };
+        let yalign = if metric.width != 0 && metric.height != 0 {
+            let yalign = baseline - metric.ymin - metric.height as i32 + source.y_offset as i32;
+            let bottom = yalign + metric.height as i32;
+            if yalign < 0 || bottom > line_height as i32 {
+                clipped.push(ClippedGlyph {
+                    char_id: *char_id,
+                    chara: *chara,
+                    above: (-yalign).max(0) as u32,
+                    below: (bottom - line_height as i32).max(0) as u32,
+                });
+            };
+            yalign
+        } else {
+            baseline - 1
+        };
+        let (unk4, unk5) = options.unk.get(*char_id);
+        let char_data = CharData::new(
+            *char_id,
+            char_image,
+            metric.xmin as i16 + source.x_offset,
+            yalign as i16,
+            metric.advance_width as u16,
+            unk4,
+            unk5,
+        )?;
+        font.chars.insert(*char_id, char_data);
+    }
+    if options.missing == MissingGlyph::Fail && !missing.is_empty() {
+        return Err(FontToolError::MissingGlyphs(
+            missing.iter().map(|(_, chara)| *chara).collect(),
+        ));
+    };
+    Ok(TrueTypeImport {
+        font,
+        baseline,
+        line_height,
+        clipped,
+        from_fallback,
+        missing,
+    })
+}
@@ -0,0 +1,425 @@
+use crate::{default_line_height, estimate_baseline, Font, FontToolError};
+use std::convert::{TryFrom, TryInto};
+
+/// The size of a pixel, in font units
+const PIXEL_SIZE: i32 = 64;
+
+/// A glyph converted to outlines, with each contour being a rectangle
+struct OutlineGlyph {
+    /// (x min, y min, x max, y max), in font units
+    rectangles: Vec<(i32, i32, i32, i32)>,
+    advance: u16,
+}
+
+impl OutlineGlyph {
+    fn bounds(&self) -> Option<(i32, i32, i32, i32)> {
+        self.rectangles.iter().fold(None, |bounds, rect| {
+            Some(match bounds {
+                None => *rect,
+                Some((x_min, y_min, x_max, y_max)) => (
+                    x_min.min(rect.0),
+                    y_min.min(rect.1),
+                    x_max.max(rect.2),
+                    y_max.max(rect.3),
+                ),
+            })
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, FontToolError> {
+        let (x_min, y_min, x_max, y_max) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return Ok(Vec::new()),
+        };
+        let mut data = Vec::new();
+        push_i16(&mut data, self.rectangles.len() as i32)?;
+        for value in &[x_min, y_min, x_max, y_max] {
+            push_i16(&mut data, *value)?;
+        }
+        for index in 0..self.rectangles.len() {
+            push_u16(&mut data, (index * 4 + 3) as u32)?;
+        }
+        // instructions length
+        push_u16(&mut data, 0)?;
+        // every point is on the curve, with the coordinates stored as 16 bit delta
+        data.resize(data.len() + self.rectangles.len() * 4, 1);
+        // the rectangles are drawn clockwise
+        let points: Vec<(i32, i32)> = self
+            .rectangles
+            .iter()
+            .flat_map(|(x0, y0, x1, y1)| vec![(*x0, *y0), (*x0, *y1), (*x1, *y1), (*x1, *y0)])
+            .collect();
+        let mut previous = 0;
+        for (x, _) in &points {
+            push_i16(&mut data, x - previous)?;
+            previous = *x;
+        }
+        previous = 0;
+        for (_, y) in &points {
+            push_i16(&mut data, y - previous)?;
+            previous = *y;
+        }
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+        Ok(data)
+    }
+}
+
+fn push_u16(data: &mut Vec<u8>, value: u32) -> Result<(), FontToolError> {
+    let value = u16::try_from(value).map_err(|_| {
+        FontToolError::TrueTypeExportError("a count, an offset or a size doesn't fit in 16 bit")
+    })?;
+    data.extend_from_slice(&value.to_be_bytes());
+    Ok(())
+}
+
+fn push_i16(data: &mut Vec<u8>, value: i32) -> Result<(), FontToolError> {
+    let value = i16::try_from(value).map_err(|_| {
+        FontToolError::TrueTypeExportError(
+            "a coordinate or a metric of the font doesn't fit in 16 bit",
+        )
+    })?;
+    data.extend_from_slice(&value.to_be_bytes());
+    Ok(())
+}
+
+fn push_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_be_bytes());
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+fn name_table(family: &str) -> Result<Vec<u8>, FontToolError> {
+    let records: [(u16, String); 6] = [
+        (1, family.to_string()),
+        (2, "Regular".to_string()),
+        (3, format!("{} Regular", family)),
+        (4, family.to_string()),
+        (5, "Version 1.0".to_string()),
+        (
+            6,
+            family
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .collect(),
+        ),
+    ];
+    let mut strings = Vec::new();
+    let mut data = Vec::new();
+    push_u16(&mut data, 0)?;
+    push_u16(&mut data, records.len() as u32)?;
+    push_u16(&mut data, 6 + 12 * records.len() as u32)?;
+    for (name_id, value) in &records {
+        let encoded: Vec<u8> = value.encode_utf16().flat_map(|c| c.to_be_bytes()).collect();
+        // windows platform, unicode BMP encoding, english
+        for field in &[
+            3,
+            1,
+            0x409,
+            *name_id as u32,
+            encoded.len() as u32,
+            strings.len() as u32,
+        ] {
+            push_u16(&mut data, *field)?;
+        }
+        strings.extend(encoded);
+    }
+    data.extend(strings);
+    Ok(data)
+}
+
+fn cmap_table(char_ids: &[u16]) -> Result<Vec<u8>, FontToolError> {
+    // segments of consecutive characters, as (start, end, first glyph id)
+    let mut segments: Vec<(u32, u32, u32)> = Vec::new();
+    for (index, char_id) in char_ids.iter().enumerate() {
+        let char_id = *char_id as u32;
+        let glyph_id = index as u32 + 1;
+        match segments.last_mut() {
+            Some(segment) if segment.1 + 1 == char_id => segment.1 = char_id,
+            _ => segments.push((char_id, char_id, glyph_id)),
+        }
+    }
+    segments.push((0xFFFF, 0xFFFF, 0));
+
+    let segment_count = segments.len() as u32;
+    let mut search_range = 2;
+    let mut entry_selector = 0;
+    while search_range * 2 <= segment_count * 2 {
+        search_range *= 2;
+        entry_selector += 1;
+    }
+    let mut subtable = Vec::new();
+    push_u16(&mut subtable, 4)?;
+    push_u16(&mut subtable, 16 + segment_count * 8)?;
+    push_u16(&mut subtable, 0)?;
+    push_u16(&mut subtable, segment_count * 2)?;
+    push_u16(&mut subtable, search_range)?;
+    push_u16(&mut subtable, entry_selector)?;
+    push_u16(&mut subtable, segment_count * 2 - search_range)?;
+    for segment in &segments {
+        push_u16(&mut subtable, segment.1)?;
+    }
+    push_u16(&mut subtable, 0)?;
+    for segment in &segments {
+        push_u16(&mut subtable, segment.0)?;
+    }
+    for segment in &segments {
+        // the delta is added modulo 65536
+        let delta = if segment.2 == 0 {
+            1
+        } else {
+            segment.2.wrapping_sub(segment.0) & 0xFFFF
+        };
+        push_u16(&mut subtable, delta)?;
+    }
+    for _ in &segments {
+        push_u16(&mut subtable, 0)?;
+    }
+
+    let mut data = Vec::new();
+    push_u16(&mut data, 0)?;
+    push_u16(&mut data, 2)?;
+    // unicode BMP, then windows unicode BMP, both pointing to the same subtable
+    for (platform, encoding) in &[(0, 3), (3, 1)] {
+        push_u16(&mut data, *platform)?;
+        push_u16(&mut data, *encoding)?;
+        push_u32(&mut data, 4 + 8 * 2);
+    }
+    data.extend(subtable);
+    Ok(data)
+}
+
+/// Convert the font to a TrueType font, where each pixel with an alpha of at least `threshold`
+/// is a square. The distance of the glyphs is used as their advance, and the baseline is guessed
+/// with [`estimate_baseline`]. A pixel is 64 font units, and the em is the height of a line.
+pub fn export_truetype(font: &Font, family: &str, threshold: u8) -> Result<Vec<u8>, FontToolError> {
+    let ascent = estimate_baseline(font) as i32;
+    let line_height = (default_line_height(font) as i32).max(1);
+    let descent = (line_height - ascent).max(0);
+    // 0xFFFF is used to end the character map
+    let char_ids: Vec<u16> = font
+        .chars
+        .keys()
+        .copied()
+        .filter(|c| *c != 0xFFFF)
+        .collect();
+
+    let mut glyphs = vec![OutlineGlyph {
+        rectangles: Vec::new(),
+        advance: u16::try_from(line_height * PIXEL_SIZE / 2).map_err(|_| {
+            FontToolError::TrueTypeExportError("the line height of the font is too big")
+        })?,
+    }];
+    for char_id in &char_ids {
+        let char_data = &font.chars[char_id];
+        let mut rectangles = Vec::new();
+        for (y, row) in char_data.image.rows().enumerate() {
+            let y_top = (ascent - char_data.yalign as i32 - y as i32) * PIXEL_SIZE;
+            // merge the consecutive pixels of a row
+            let mut run_start = None;
+            for (x, pixel) in row
+                .chain(std::iter::once(&image::Rgba([0, 0, 0, 0])))
+                .enumerate()
+            {
+                match (pixel[3] >= threshold && pixel[3] != 0, run_start) {
+                    (true, None) => run_start = Some(x),
+                    (false, Some(start)) => {
+                        let x_start = (char_data.xalign as i32 + start as i32) * PIXEL_SIZE;
+                        let x_end = (char_data.xalign as i32 + x as i32) * PIXEL_SIZE;
+                        rectangles.push((x_start, y_top - PIXEL_SIZE, x_end, y_top));
+                        run_start = None;
+                    }
+                    _ => (),
+                }
+            }
+        }
+        let advance = (char_data.distance as i32 * PIXEL_SIZE)
+            .try_into()
+            .map_err(|_| FontToolError::TrueTypeAdvanceTooBig(*char_id, char_data.distance))?;
+        glyphs.push(OutlineGlyph {
+            rectangles,
+            advance,
+        });
+    }
+
+    let mut glyf = Vec::new();
+    let mut loca = Vec::new();
+    let mut hmtx = Vec::new();
+    let mut font_bounds: Option<(i32, i32, i32, i32)> = None;
+    let (mut max_points, mut max_contours) = (0, 0);
+    let mut min_right_side_bearing = i32::MAX;
+    for glyph in &glyphs {
+        push_u32(&mut loca, glyf.len() as u32);
+        glyf.extend(glyph.encode()?);
+        let bounds = glyph.bounds();
+        push_u16(&mut hmtx, glyph.advance as u32)?;
+        push_i16(&mut hmtx, bounds.map_or(0, |b| b.0))?;
+        if let Some(bounds) = bounds {
+            min_right_side_bearing = min_right_side_bearing.min(glyph.advance as i32 - bounds.2);
+            font_bounds = Some(match font_bounds {
+                None => bounds,
+                Some(f) => (
+                    f.0.min(bounds.0),
+                    f.1.min(bounds.1),
+                    f.2.max(bounds.2),
+                    f.3.max(bounds.3),
+                ),
+            });
+        };
+        max_points = max_points.max(glyph.rectangles.len() * 4);
+        max_contours = max_contours.max(glyph.rectangles.len());
+    }
+    push_u32(&mut loca, glyf.len() as u32);
+    let font_bounds = font_bounds.unwrap_or((0, 0, 0, 0));
+    let advance_max = glyphs.iter().map(|g| g.advance).max().unwrap_or(0);
+    if max_points > u16::MAX as usize {
+        return Err(FontToolError::TrueTypeExportError(
+            "a glyph have too many pixels",
+        ));
+    };
+
+    let units_per_em = (line_height * PIXEL_SIZE) as u32;
+    let mut head = Vec::new();
+    push_u32(&mut head, 0x0001_0000);
+    push_u32(&mut head, 0x0001_0000);
+    push_u32(&mut head, 0); // checksum adjustment, set later
+    push_u32(&mut head, 0x5F0F_3CF5);
+    push_u16(&mut head, 0b1011)?;
+    push_u16(&mut head, units_per_em)?;
+    head.extend_from_slice(&[0; 16]); // created and modified dates
+    for value in &[font_bounds.0, font_bounds.1, font_bounds.2, font_bounds.3] {
+        push_i16(&mut head, *value)?;
+    }
+    push_u16(&mut head, 0)?; // mac style
+    push_u16(&mut head, line_height as u32)?; // lowest recommended ppem
+    push_i16(&mut head, 2)?; // font direction hint
+    push_i16(&mut head, 1)?; // long loca offsets
+    push_i16(&mut head, 0)?;
+
+    let mut hhea = Vec::new();
+    push_u32(&mut hhea, 0x0001_0000);
+    push_i16(&mut hhea, ascent * PIXEL_SIZE)?;
+    push_i16(&mut hhea, -descent * PIXEL_SIZE)?;
+    push_i16(&mut hhea, 0)?; // line gap
+    push_u16(&mut hhea, advance_max as u32)?;
+    push_i16(&mut hhea, font_bounds.0.min(0))?;
+    push_i16(&mut hhea, min_right_side_bearing.min(0))?;
+    push_i16(&mut hhea, font_bounds.2)?;
+    push_i16(&mut hhea, 1)?; // caret slope rise
+    hhea.extend_from_slice(&[0; 2 * 2 + 4 * 2 + 2]); // caret, reserved, metric format
+    push_u16(&mut hhea, glyphs.len() as u32)?;
+
+    let mut maxp = Vec::new();
+    push_u32(&mut maxp, 0x0001_0000);
+    push_u16(&mut maxp, glyphs.len() as u32)?;
+    push_u16(&mut maxp, max_points as u32)?;
+    push_u16(&mut maxp, max_contours as u32)?;
+    push_u32(&mut maxp, 0); // composite points and contours
+    push_u16(&mut maxp, 2)?; // zones
+    maxp.extend_from_slice(&[0; 2 * 8]);
+
+    let mut os2 = Vec::new();
+    push_u16(&mut os2, 4)?;
+    let average_width = glyphs.iter().map(|g| g.advance as u32).sum::<u32>() / glyphs.len() as u32;
+    push_i16(&mut os2, average_width as i32)?;
+    push_u16(&mut os2, 400)?; // weight
+    push_u16(&mut os2, 5)?; // width
+    push_u16(&mut os2, 0)?; // embedding allowed
+    let em = units_per_em as i32;
+    for value in &[
+        em / 2,
+        em / 2,
+        0,
+        em / 10,
+        em / 2,
+        em / 2,
+        0,
+        em / 3,
+        PIXEL_SIZE,
+        em / 4,
+    ] {
+        push_i16(&mut os2, *value)?;
+    }
+    push_i16(&mut os2, 0)?; // family class
+    os2.extend_from_slice(&[0; 10]); // panose
+    os2.extend_from_slice(&[0; 16]); // unicode ranges
+    os2.extend_from_slice(b"PMDT");
+    push_u16(&mut os2, 0x40)?; // regular
+    push_u16(&mut os2, char_ids.first().copied().unwrap_or(0) as u32)?;
+    push_u16(&mut os2, char_ids.last().copied().unwrap_or(0) as u32)?;
+    push_i16(&mut os2, ascent * PIXEL_SIZE)?;
+    push_i16(&mut os2, -descent * PIXEL_SIZE)?;
+    push_i16(&mut os2, 0)?;
+    push_u16(&mut os2, font_bounds.3.max(ascent * PIXEL_SIZE) as u32)?;
+    push_u16(&mut os2, (-font_bounds.1).max(descent * PIXEL_SIZE) as u32)?;
+    push_u32(&mut os2, 1); // latin 1 code page
+    push_u32(&mut os2, 0);
+    push_i16(&mut os2, em / 2)?; // x height
+    push_i16(&mut os2, ascent * PIXEL_SIZE)?; // cap height
+    push_u16(&mut os2, 0)?;
+    push_u16(&mut os2, 32)?;
+    push_u16(&mut os2, 1)?;
+
+    let mut post = Vec::new();
+    push_u32(&mut post, 0x0003_0000);
+    push_u32(&mut post, 0); // italic angle
+    push_i16(&mut post, -PIXEL_SIZE)?;
+    push_i16(&mut post, PIXEL_SIZE)?;
+    post.extend_from_slice(&[0; 4 * 5]);
+
+    let mut tables: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"OS/2", os2),
+        (b"cmap", cmap_table(&char_ids)?),
+        (b"glyf", glyf),
+        (b"head", head),
+        (b"hhea", hhea),
+        (b"hmtx", hmtx),
+        (b"loca", loca),
+        (b"maxp", maxp),
+        (b"name", name_table(family)?),
+        (b"post", post),
+    ];
+    tables.sort_by_key(|(tag, _)| **tag);
+
+    let table_count = tables.len() as u32;
+    let mut search_range = 1;
+    let mut entry_selector = 0;
+    while search_range * 2 <= table_count {
+        search_range *= 2;
+        entry_selector += 1;
+    }
+    let mut result = Vec::new();
+    push_u32(&mut result, 0x0001_0000);
+    push_u16(&mut result, table_count)?;
+    push_u16(&mut result, search_range * 16)?;
+    push_u16(&mut result, entry_selector)?;
+    push_u16(&mut result, table_count * 16 - search_range * 16)?;
+    let mut offset = 12 + 16 * table_count;
+    let mut head_offset = 0;
+    for (tag, data) in &tables {
+        if *tag == b"head" {
+            head_offset = offset as usize;
+        };
+        result.extend_from_slice(*tag);
+        push_u32(&mut result, checksum(data));
+        push_u32(&mut result, offset);
+        push_u32(&mut result, data.len() as u32);
+        offset += (data.len() as u32).div_ceil(4) * 4;
+    }
+    for (_, data) in &tables {
+        result.extend_from_slice(data);
+        while result.len() % 4 != 0 {
+            result.push(0);
+        }
+    }
+    let adjustment = 0xB1B0_AFBAu32.wrapping_sub(checksum(&result));
+    result[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    Ok(result)
+}
@@ -0,0 +1,79 @@
+use crate::{parse_codepoint, FontToolError};
+use pmd_dic::KandFile;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::io::{Read, Seek};
+
+/// The unk4 value given to generated glyphs when nothing else is known
+pub const DEFAULT_UNK4: u16 = 10;
+/// The unk5 value given to generated glyphs when nothing else is known
+pub const DEFAULT_UNK5: u16 = 10;
+
+/// The unk4 and unk5 values to give to the glyphs of a generated font
+#[derive(Debug, Clone)]
+pub struct UnkValues {
+    /// the unk4 value of the characters without override
+    pub unk4: u16,
+    /// the unk5 value of the characters without override
+    pub unk5: u16,
+    /// the unk4 and unk5 values of specific characters, indexed by their id
+    pub overrides: BTreeMap<u16, (u16, u16)>,
+}
+
+impl Default for UnkValues {
+    fn default() -> Self {
+        Self {
+            unk4: DEFAULT_UNK4,
+            unk5: DEFAULT_UNK5,
+            overrides: BTreeMap::new(),
+        }
+    }
+}
+
+impl UnkValues {
+    /// Return the unk4 and unk5 values of a character
+    pub fn get(&self, char_id: u16) -> (u16, u16) {
+        self.overrides
+            .get(&char_id)
+            .copied()
+            .unwrap_or((self.unk4, self.unk5))
+    }
+
+    /// Override the values of every character of a reference .dic file with the ones it use
+    pub fn copy_from_dic<R: Read + Seek>(&mut self, dic: &mut R) -> Result<(), FontToolError> {
+        let kand = KandFile::new_from_reader(dic)?;
+        for kand_char in kand.chars {
+            self.overrides
+                .insert(kand_char.char, (kand_char.unk4, kand_char.unk5));
+        }
+        Ok(())
+    }
+
+    /// Read overrides from a text file.
+    ///
+    /// Each line contain the id of the character (like `U+0041`), its unk4 then its unk5 values,
+    /// separated by spaces. Empty lines and text after a `#` are ignored.
+    pub fn read_overrides<R: Read>(&mut self, reader: &mut R) -> Result<(), FontToolError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        for (line_index, line) in text.lines().enumerate() {
+            let content = line.split('#').next().unwrap_or("").trim();
+            if content.is_empty() {
+                continue;
+            };
+            let invalid =
+                || FontToolError::InvalidUnkOverrideLine(line_index + 1, line.to_string());
+            let parts: Vec<&str> = content.split_whitespace().collect();
+            if parts.len() != 3 {
+                return Err(invalid());
+            };
+            let char_id: u16 = parse_codepoint(parts[0])
+                .and_then(|codepoint| codepoint.try_into().ok())
+                .ok_or_else(invalid)?;
+            let unk4 = parts[1].parse().map_err(|_| invalid())?;
+            let unk5 = parts[2].parse().map_err(|_| invalid())?;
+            self.overrides.insert(char_id, (unk4, unk5));
+        }
+        Ok(())
+    }
+}
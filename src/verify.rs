@@ -0,0 +1,13 @@
+use crate::folder::{folder_files, font_from_folder_files};
+use crate::{AtlasOptions, Font, FontToolError};
+
+/// Simulate a generate then build cycle in memory. The font is converted to the files of a folder
+/// (the manifest and a png for each glyph) and read back from them, like the generate and build
+/// commands do, then written to a .dic and an .img file, whose content are returned.
+pub fn round_trip(font: &Font) -> Result<(Vec<u8>, Vec<u8>), FontToolError> {
+    let rebuilt = font_from_folder_files(&folder_files(font)?)?;
+    let mut dic = Vec::new();
+    let mut img = Vec::new();
+    rebuilt.save(&mut dic, &mut img, &AtlasOptions::default())?;
+    Ok((dic, img))
+}